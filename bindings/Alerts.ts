@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export interface Alerts { id: number, active: boolean, name: string, table: string, lookup: string, timing: number, warn: string, crit: string, info: string | null, host_uuid: string, cid: string, hostname: string, where_clause: string | null, }
\ No newline at end of file
+export interface Alerts { id: number, active: boolean, name: string, table: string, lookup: string, timing: number, warn: string, crit: string, info: string | null, host_uuid: string, cid: string, hostname: string, where_clause: string | null, flap_window: number | null, flap_threshold: number | null, deleted_at: string | null, }
\ No newline at end of file
@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export interface AlertsDTOUpdate { active: boolean | null, name: string | null, table: string | null, lookup: string | null, timing: number | null, warn: string | null, crit: string | null, info: string | null, where_clause: string | null, }
\ No newline at end of file
+export interface AlertsDTOUpdate { active: boolean | null, name: string | null, table: string | null, lookup: string | null, timing: number | null, warn: string | null, crit: string | null, info: string | null, where_clause: string | null, flap_window: number | null, flap_threshold: number | null, }
\ No newline at end of file
@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export interface ApiKey { id: number, key: string, host_uuid: string | null, customer_id: string, berta: string, }
\ No newline at end of file
+export interface ApiKey { id: number, host_uuid: string | null, customer_id: string, berta: string, prefix: string, scopes: Array<string>, created_at: string, expires_at: string | null, last_used_at: string | null, revoked_at: string | null, enrolled: boolean, }
\ No newline at end of file
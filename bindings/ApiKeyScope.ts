@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ApiKeyScope = "ingest" | "read_metrics" | "manage_alerts" | "read_incidents" | "admin";
\ No newline at end of file
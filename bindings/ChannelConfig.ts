@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ChannelConfig = { "type": "email", recipients: Array<string>, oncall: number | null, } | { "type": "webhook", url: string, secret: string, } | { "type": "slack", url: string, } | { "type": "discord", url: string, } | { "type": "teams", url: string, } | { "type": "mattermost", url: string, } | { "type": "alertmanager", url: string, } | { "type": "pagerduty", routing_key: string, url: string | null, };
\ No newline at end of file
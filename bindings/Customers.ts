@@ -1,3 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { NotificationPreferences } from "./NotificationPreferences";
 
-export interface Customers { id: string, email: string, }
\ No newline at end of file
+export interface Customers { id: string, email: string, display_name: string | null, timezone: string, notification_prefs: NotificationPreferences, pending_email: string | null, totp_enabled_at: string | null, }
\ No newline at end of file
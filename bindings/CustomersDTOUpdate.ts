@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { NotificationPreferences } from "./NotificationPreferences";
+
+export interface CustomersDTOUpdate { display_name: string | null | null, timezone: string | null, notification_prefs: NotificationPreferences | null, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface DigestSubscriptions { id: number, cid: string, period: number, recipients: Array<string>, min_severity: number, active: boolean, last_sent_at: string | null, created_at: string, }
\ No newline at end of file
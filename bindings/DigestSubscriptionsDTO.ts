@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface DigestSubscriptionsDTO { cid: string, period: number | null, recipients: Array<string> | null, min_severity: number | null, active: boolean | null, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface EnrollmentTokens { id: number, cid: string, name: string, prefix: string, berta: string, max_uses: number, uses: number, expires_at: string, revoked_at: string | null, created_at: string, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface EnrollmentTokensRequest { cid: string, name: string, berta: string, max_uses: number | null, ttl: bigint, }
\ No newline at end of file
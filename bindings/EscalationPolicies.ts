@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EscalationSteps } from "./EscalationSteps";
+
+export interface EscalationPolicies { id: number, cid: string, name: string, steps: EscalationSteps, min_severity: number, alerts_id: number | null, host_uuid: string | null, created_at: string, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EscalationSteps } from "./EscalationSteps";
+
+export interface EscalationPoliciesDTO { cid: string, name: string, steps: EscalationSteps, min_severity: number | null, alerts_id: number | null, host_uuid: string | null, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EscalationSteps } from "./EscalationSteps";
+
+export interface EscalationPoliciesDTOUpdate { name: string | null, steps: EscalationSteps | null, min_severity: number | null, }
\ No newline at end of file
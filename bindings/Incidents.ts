@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export interface Incidents { id: number, result: string, started_at: string, updated_at: string, resolved_at: string | null, host_uuid: string, hostname: string, status: number, severity: number, alerts_id: bigint, cid: string, }
\ No newline at end of file
+export interface Incidents { id: number, result: string, started_at: string, updated_at: string, resolved_at: string | null, host_uuid: string, hostname: string, status: number, severity: number, alerts_id: bigint, cid: string, acknowledged_at: string | null, flapping: boolean, flap_changes: number, flap_changed_at: string | null, dimension: string, escalation_step: number, }
\ No newline at end of file
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { IncidentsSortKey } from "./IncidentsSortKey";
+import type { SortDirection } from "./SortDirection";
+
+export interface IncidentsFilter { status: Array<number> | null, severity: Array<number> | null, hosts: Array<string> | null, alerts_id: number | null, started_after: string | null, started_before: string | null, resolved_after: string | null, resolved_before: string | null, search: string | null, sort: IncidentsSortKey, direction: SortDirection, }
\ No newline at end of file
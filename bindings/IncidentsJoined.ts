@@ -1,4 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { Alerts } from "./Alerts";
 
-export interface IncidentsJoined { id: number, result: string, started_at: string, updated_at: string, resolved_at: string | null, host_uuid: string, hostname: string, status: number, severity: number, alerts_id: bigint, cid: string, alert: Alerts | null, }
\ No newline at end of file
+export interface IncidentsJoined { id: number, result: string, started_at: string, updated_at: string, resolved_at: string | null, host_uuid: string, hostname: string, status: number, severity: number, alerts_id: bigint, cid: string, acknowledged_at: string | null, flapping: boolean, flap_changes: number, flap_changed_at: string | null, dimension: string, escalation_step: number, alert: Alerts | null, alert_exists: boolean, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ChannelConfig } from "./ChannelConfig";
+
+export interface NotificationChannels { id: number, cid: string, name: string, config: ChannelConfig, active: boolean, min_severity: number, events: Array<string>, host_uuid: string | null, created_at: string, }
\ No newline at end of file
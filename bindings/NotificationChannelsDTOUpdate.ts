@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ChannelConfig } from "./ChannelConfig";
+
+export interface NotificationChannelsDTOUpdate { name: string | null, config: ChannelConfig | null, active: boolean | null, min_severity: number | null, events: Array<string> | null, host_uuid: string | null, }
\ No newline at end of file
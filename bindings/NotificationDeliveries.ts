@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface NotificationDeliveries { id: number, channel_id: number, incident_id: number, cid: string, event: string, status: number, attempts: number, response_code: number | null, last_error: string | null, next_retry_at: string | null, created_at: string, updated_at: string, }
\ No newline at end of file
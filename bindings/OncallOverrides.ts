@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface OncallOverrides { id: number, schedule_id: number, user_id: string, starts_at: string, ends_at: string, created_at: string, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface OncallSchedules { id: number, cid: string, name: string, timezone: string, rotation: number, handoff_time: string, start_date: string, members: Array<string>, created_at: string, }
\ No newline at end of file
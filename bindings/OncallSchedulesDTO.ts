@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface OncallSchedulesDTO { cid: string, name: string, timezone: string | null, rotation: number | null, handoff_time: string | null, start_date: string, members: Array<string>, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface OncallSchedulesDTOUpdate { name: string | null, timezone: string | null, rotation: number | null, handoff_time: string | null, start_date: string | null, members: Array<string> | null, }
\ No newline at end of file
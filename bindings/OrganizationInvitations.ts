@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface OrganizationInvitations { id: number, org_id: string, email: string, invited_by: string, expires_at: string, accepted_at: string | null, created_at: string, role: string, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface OrganizationMembers { org_id: string, customer_id: string, created_at: string, role: string, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface WebhookAlert { id: number, name: string, lookup: string, warn: string, crit: string, info: string | null, }
\ No newline at end of file
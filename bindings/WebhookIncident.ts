@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { WebhookAlert } from "./WebhookAlert";
+
+export interface WebhookIncident { id: number, result: string, started_at: string, updated_at: string, acknowledged_at: string | null, resolved_at: string | null, status: number, severity: number, flapping: boolean, host_uuid: string, hostname: string, dimension: string, alerts_id: number, cid: string, alert_exists: boolean, alert: WebhookAlert | null, }
\ No newline at end of file
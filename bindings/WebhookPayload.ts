@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { IncidentEvent } from "./IncidentEvent";
+import type { WebhookIncident } from "./WebhookIncident";
+
+export interface WebhookPayload { version: number, event: IncidentEvent, sent_at: string, incident: WebhookIncident, }
\ No newline at end of file
@@ -15,4 +15,56 @@ export * from "./Swap"
 export * from "./Memory"
 export * from "./AlertsDTO"
 export * from "./Disk"
-export * from "./Alerts"
\ No newline at end of file
+export * from "./Alerts"
+export * from "./HttpIncidentsDailyCount"
+export * from "./HttpIncidentsMeanTimes"
+export * from "./HttpIncidentsSeverityCount"
+export * from "./HttpIncidentsTopAlert"
+export * from "./IncidentsStatsGroup"
+export * from "./IncidentStatus"
+export * from "./IncidentsFilter"
+export * from "./IncidentsSortKey"
+export * from "./SortDirection"
+export * from "./IncidentsExportFormat"
+export * from "./IncidentsRetention"
+export * from "./ChannelConfig"
+export * from "./IncidentEvent"
+export * from "./NotificationChannels"
+export * from "./NotificationChannelsDTO"
+export * from "./NotificationChannelsDTOUpdate"
+export * from "./IncidentSeverity"
+export * from "./WebhookPayload"
+export * from "./DeliveryStatus"
+export * from "./NotificationDeliveries"
+export * from "./EscalationPolicies"
+export * from "./EscalationPoliciesDTO"
+export * from "./EscalationPoliciesDTOUpdate"
+export * from "./EscalationStep"
+export * from "./EscalationSteps"
+export * from "./OncallOverrides"
+export * from "./OncallOverridesDTO"
+export * from "./OncallRotation"
+export * from "./OncallSchedules"
+export * from "./OncallSchedulesDTO"
+export * from "./OncallSchedulesDTOUpdate"
+export * from "./DigestPeriod"
+export * from "./DigestSubscriptions"
+export * from "./DigestSubscriptionsDTO"
+export * from "./DigestSubscriptionsDTOUpdate"
+export * from "./GeneratedApiKey"
+export * from "./ApiKeyScope"
+export * from "./EnrollmentTokens"
+export * from "./EnrollmentTokensRequest"
+export * from "./GeneratedEnrollmentToken"
+export * from "./CustomersDTOUpdate"
+export * from "./NotificationPreferences"
+export * from "./GeneratedInvitation"
+export * from "./OrganizationInvitations"
+export * from "./OrganizationMembers"
+export * from "./Organizations"
+export * from "./OrganizationsDTO"
+export * from "./Action"
+export * from "./Role"
+export * from "./TotpEnrollment"
+export * from "./WebhookAlert"
+export * from "./WebhookIncident"
\ No newline at end of file
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS incidents_cid_started_at_idx;
+
+ALTER TABLE incidents DROP COLUMN acknowledged_at;
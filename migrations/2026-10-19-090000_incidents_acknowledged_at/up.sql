@@ -0,0 +1,3 @@
+ALTER TABLE incidents ADD COLUMN acknowledged_at TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS incidents_cid_started_at_idx ON incidents (cid, started_at);
@@ -0,0 +1,8 @@
+DROP INDEX IF EXISTS incidents_alerts_id_resolved_at_idx;
+
+ALTER TABLE incidents DROP COLUMN flap_changed_at;
+ALTER TABLE incidents DROP COLUMN flap_changes;
+ALTER TABLE incidents DROP COLUMN flapping;
+
+ALTER TABLE alerts DROP COLUMN flap_threshold;
+ALTER TABLE alerts DROP COLUMN flap_window;
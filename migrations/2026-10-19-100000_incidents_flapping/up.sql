@@ -0,0 +1,8 @@
+ALTER TABLE alerts ADD COLUMN flap_window INT4;
+ALTER TABLE alerts ADD COLUMN flap_threshold INT4;
+
+ALTER TABLE incidents ADD COLUMN flapping BOOL NOT NULL DEFAULT false;
+ALTER TABLE incidents ADD COLUMN flap_changes INT4 NOT NULL DEFAULT 0;
+ALTER TABLE incidents ADD COLUMN flap_changed_at TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS incidents_alerts_id_resolved_at_idx ON incidents (alerts_id, resolved_at);
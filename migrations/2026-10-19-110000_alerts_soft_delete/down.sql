@@ -0,0 +1,3 @@
+DELETE FROM alerts WHERE deleted_at IS NOT NULL;
+
+ALTER TABLE alerts DROP COLUMN deleted_at;
@@ -0,0 +1 @@
+ALTER TABLE alerts ADD COLUMN deleted_at TIMESTAMP;
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS incidents_one_active_idx;
+
+ALTER TABLE incidents DROP COLUMN dimension;
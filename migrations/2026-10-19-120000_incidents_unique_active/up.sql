@@ -0,0 +1,25 @@
+ALTER TABLE incidents ADD COLUMN dimension VARCHAR NOT NULL DEFAULT '';
+
+-- Merge the duplicated active incidents into the oldest one
+WITH groups AS (
+	SELECT
+		(ARRAY_AGG(id ORDER BY started_at, id))[1] as keep_id,
+		ARRAY_AGG(id) as ids,
+		MAX(updated_at) as updated_at,
+		MAX(severity) as severity,
+		(ARRAY_AGG(result ORDER BY updated_at DESC))[1] as result
+	FROM incidents
+	WHERE status=0
+	GROUP BY alerts_id, dimension
+	HAVING COUNT(*) > 1
+), merged AS (
+	UPDATE incidents i
+	SET updated_at=g.updated_at, severity=g.severity, result=g.result
+	FROM groups g
+	WHERE i.id=g.keep_id
+)
+DELETE FROM incidents i
+USING groups g
+WHERE i.id=ANY(g.ids) AND i.id <> g.keep_id;
+
+CREATE UNIQUE INDEX incidents_one_active_idx ON incidents (alerts_id, dimension) WHERE status=0;
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS incidents_cid_resolved_at_idx;
+
+DROP TABLE incidents_archive;
+DROP TABLE incidents_retention;
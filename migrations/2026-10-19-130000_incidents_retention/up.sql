@@ -0,0 +1,12 @@
+CREATE TABLE incidents_retention (
+	cid UUID PRIMARY KEY,
+	days INT4 NOT NULL CHECK (days > 0),
+	archive BOOL NOT NULL DEFAULT true
+);
+
+CREATE TABLE incidents_archive (LIKE incidents INCLUDING DEFAULTS);
+ALTER TABLE incidents_archive ADD PRIMARY KEY (id);
+ALTER TABLE incidents_archive ADD COLUMN archived_at TIMESTAMP NOT NULL DEFAULT (now() at time zone 'utc');
+
+CREATE INDEX incidents_archive_cid_idx ON incidents_archive (cid, started_at);
+CREATE INDEX IF NOT EXISTS incidents_cid_resolved_at_idx ON incidents (cid, resolved_at);
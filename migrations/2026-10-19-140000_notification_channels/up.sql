@@ -0,0 +1,15 @@
+CREATE TABLE notification_channels (
+	id BIGSERIAL PRIMARY KEY,
+	cid UUID NOT NULL,
+	_name VARCHAR NOT NULL,
+	config JSONB NOT NULL,
+	active BOOL NOT NULL DEFAULT true,
+	min_severity INT4 NOT NULL DEFAULT 0,
+	events TEXT[] NOT NULL DEFAULT '{}',
+	host_uuid VARCHAR,
+	created_at TIMESTAMP NOT NULL DEFAULT (now() at time zone 'utc'),
+	-- The webhooks are always signed
+	CONSTRAINT notification_channels_webhook_secret CHECK (config->>'type' <> 'webhook' OR config ? 'secret')
+);
+
+CREATE INDEX notification_channels_cid_idx ON notification_channels (cid);
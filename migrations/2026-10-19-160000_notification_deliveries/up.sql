@@ -0,0 +1,18 @@
+CREATE TABLE notification_deliveries (
+	id BIGSERIAL PRIMARY KEY,
+	channel_id INT8 NOT NULL REFERENCES notification_channels (id) ON DELETE CASCADE,
+	incident_id INT4 NOT NULL REFERENCES incidents (id) ON DELETE CASCADE,
+	cid UUID NOT NULL,
+	event VARCHAR NOT NULL,
+	status INT4 NOT NULL DEFAULT 0,
+	attempts INT4 NOT NULL DEFAULT 0,
+	response_code INT4,
+	last_error TEXT,
+	next_retry_at TIMESTAMP,
+	created_at TIMESTAMP NOT NULL DEFAULT (now() at time zone 'utc'),
+	updated_at TIMESTAMP NOT NULL DEFAULT (now() at time zone 'utc')
+);
+
+CREATE INDEX notification_deliveries_incident_id_idx ON notification_deliveries (incident_id);
+-- Only the pending deliveries are looked up by the workers
+CREATE INDEX notification_deliveries_queue_idx ON notification_deliveries (next_retry_at) WHERE status = 0;
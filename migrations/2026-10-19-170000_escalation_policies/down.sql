@@ -0,0 +1,4 @@
+ALTER TABLE incidents_archive DROP COLUMN escalation_step;
+ALTER TABLE incidents DROP COLUMN escalation_step;
+
+DROP TABLE escalation_policies;
@@ -0,0 +1,19 @@
+CREATE TABLE escalation_policies (
+	id BIGSERIAL PRIMARY KEY,
+	cid UUID NOT NULL,
+	_name VARCHAR NOT NULL,
+	steps JSONB NOT NULL,
+	min_severity INT4 NOT NULL DEFAULT 1,
+	alerts_id INT8 REFERENCES alerts (id) ON DELETE CASCADE,
+	host_uuid VARCHAR,
+	created_at TIMESTAMP NOT NULL DEFAULT (now() at time zone 'utc'),
+	CHECK (alerts_id IS NULL OR host_uuid IS NULL)
+);
+
+-- At most one policy per alert, per host and one default per customer
+CREATE UNIQUE INDEX escalation_policies_alert_idx ON escalation_policies (alerts_id) WHERE alerts_id IS NOT NULL;
+CREATE UNIQUE INDEX escalation_policies_host_idx ON escalation_policies (cid, host_uuid) WHERE host_uuid IS NOT NULL;
+CREATE UNIQUE INDEX escalation_policies_default_idx ON escalation_policies (cid) WHERE alerts_id IS NULL AND host_uuid IS NULL;
+
+ALTER TABLE incidents ADD COLUMN escalation_step INT4 NOT NULL DEFAULT 0;
+ALTER TABLE incidents_archive ADD COLUMN escalation_step INT4 NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+DROP TABLE oncall_overrides;
+DROP TABLE oncall_schedules;
@@ -0,0 +1,25 @@
+CREATE TABLE oncall_schedules (
+	id BIGSERIAL PRIMARY KEY,
+	cid UUID NOT NULL,
+	_name VARCHAR NOT NULL,
+	timezone VARCHAR NOT NULL DEFAULT 'UTC',
+	rotation INT4 NOT NULL DEFAULT 1,
+	handoff_time TIME NOT NULL DEFAULT '09:00',
+	start_date DATE NOT NULL,
+	members UUID[] NOT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT (now() at time zone 'utc')
+);
+
+CREATE INDEX oncall_schedules_cid_idx ON oncall_schedules (cid);
+
+CREATE TABLE oncall_overrides (
+	id BIGSERIAL PRIMARY KEY,
+	schedule_id INT8 NOT NULL REFERENCES oncall_schedules (id) ON DELETE CASCADE,
+	user_id UUID NOT NULL,
+	starts_at TIMESTAMP NOT NULL,
+	ends_at TIMESTAMP NOT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT (now() at time zone 'utc'),
+	CHECK (ends_at > starts_at)
+);
+
+CREATE INDEX oncall_overrides_schedule_idx ON oncall_overrides (schedule_id, ends_at);
@@ -0,0 +1,12 @@
+CREATE TABLE digest_subscriptions (
+	id BIGSERIAL PRIMARY KEY,
+	cid UUID NOT NULL,
+	period INT4 NOT NULL DEFAULT 0,
+	recipients TEXT[] NOT NULL DEFAULT '{}',
+	min_severity INT4 NOT NULL DEFAULT 0,
+	active BOOL NOT NULL DEFAULT true,
+	last_sent_at TIMESTAMP,
+	created_at TIMESTAMP NOT NULL DEFAULT (now() at time zone 'utc')
+);
+
+CREATE INDEX digest_subscriptions_cid_idx ON digest_subscriptions (cid);
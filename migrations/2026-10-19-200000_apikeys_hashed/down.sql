@@ -0,0 +1,6 @@
+-- The plaintext keys cannot be recovered from their hash
+DROP INDEX apikeys_prefix_idx;
+ALTER TABLE apikeys ADD COLUMN key TEXT NOT NULL DEFAULT '';
+ALTER TABLE apikeys ALTER COLUMN key DROP DEFAULT;
+ALTER TABLE apikeys DROP COLUMN key_hash;
+ALTER TABLE apikeys DROP COLUMN prefix;
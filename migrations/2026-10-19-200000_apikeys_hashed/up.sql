@@ -0,0 +1,14 @@
+ALTER TABLE apikeys ADD COLUMN prefix TEXT;
+ALTER TABLE apikeys ADD COLUMN key_hash TEXT;
+
+-- Existing (plaintext) keys don't have the prefix + secret form, the whole
+-- key is hashed and they are looked up by the first 8 characters of the hash
+-- (not of the key, which would keep a part of the secret in plaintext).
+UPDATE apikeys SET key_hash = encode(sha256(convert_to(key, 'UTF8')), 'hex');
+UPDATE apikeys SET prefix = left(key_hash, 8);
+
+ALTER TABLE apikeys ALTER COLUMN prefix SET NOT NULL;
+ALTER TABLE apikeys ALTER COLUMN key_hash SET NOT NULL;
+ALTER TABLE apikeys DROP COLUMN key;
+
+CREATE INDEX apikeys_prefix_idx ON apikeys (prefix);
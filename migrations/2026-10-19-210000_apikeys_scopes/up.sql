@@ -0,0 +1,2 @@
+-- Existing keys keep the only power they had: pushing metrics
+ALTER TABLE apikeys ADD COLUMN scopes TEXT[] NOT NULL DEFAULT '{ingest}';
@@ -0,0 +1,4 @@
+ALTER TABLE apikeys DROP COLUMN revoked_at;
+ALTER TABLE apikeys DROP COLUMN last_used_at;
+ALTER TABLE apikeys DROP COLUMN expires_at;
+ALTER TABLE apikeys DROP COLUMN created_at;
@@ -0,0 +1,4 @@
+ALTER TABLE apikeys ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT (now() at time zone 'utc');
+ALTER TABLE apikeys ADD COLUMN expires_at TIMESTAMP;
+ALTER TABLE apikeys ADD COLUMN last_used_at TIMESTAMP;
+ALTER TABLE apikeys ADD COLUMN revoked_at TIMESTAMP;
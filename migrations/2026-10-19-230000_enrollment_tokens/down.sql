@@ -0,0 +1,3 @@
+DROP INDEX apikeys_enrolled_host_uuid_idx;
+ALTER TABLE apikeys DROP COLUMN enrolled;
+DROP TABLE enrollment_tokens;
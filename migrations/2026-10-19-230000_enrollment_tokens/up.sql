@@ -0,0 +1,23 @@
+CREATE TABLE enrollment_tokens (
+	id BIGSERIAL PRIMARY KEY,
+	cid UUID NOT NULL,
+	_name VARCHAR NOT NULL,
+	prefix TEXT NOT NULL,
+	token_hash TEXT NOT NULL,
+	berta TEXT NOT NULL,
+	max_uses INT4 NOT NULL DEFAULT 1,
+	uses INT4 NOT NULL DEFAULT 0,
+	expires_at TIMESTAMP NOT NULL,
+	revoked_at TIMESTAMP,
+	created_at TIMESTAMP NOT NULL DEFAULT (now() at time zone 'utc'),
+	CHECK (max_uses > 0)
+);
+
+CREATE INDEX enrollment_tokens_cid_idx ON enrollment_tokens (cid);
+CREATE INDEX enrollment_tokens_prefix_idx ON enrollment_tokens (prefix);
+
+-- Only the keys created by an enrollment are unique per host (of an organization),
+-- the rotated or manually created ones can share the host of another key.
+ALTER TABLE apikeys ADD COLUMN enrolled BOOLEAN NOT NULL DEFAULT false;
+
+CREATE UNIQUE INDEX apikeys_enrolled_host_uuid_idx ON apikeys (customer_id, host_uuid) WHERE enrolled AND revoked_at IS NULL;
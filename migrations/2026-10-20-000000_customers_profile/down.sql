@@ -0,0 +1,7 @@
+DROP INDEX customers_email_token_idx;
+ALTER TABLE customers DROP COLUMN email_token_expires_at;
+ALTER TABLE customers DROP COLUMN email_token_hash;
+ALTER TABLE customers DROP COLUMN pending_email;
+ALTER TABLE customers DROP COLUMN notification_prefs;
+ALTER TABLE customers DROP COLUMN timezone;
+ALTER TABLE customers DROP COLUMN display_name;
@@ -0,0 +1,9 @@
+ALTER TABLE customers ADD COLUMN display_name VARCHAR;
+ALTER TABLE customers ADD COLUMN timezone VARCHAR NOT NULL DEFAULT 'UTC';
+ALTER TABLE customers ADD COLUMN notification_prefs JSONB NOT NULL DEFAULT '{}';
+-- Email change waiting for its confirmation
+ALTER TABLE customers ADD COLUMN pending_email VARCHAR;
+ALTER TABLE customers ADD COLUMN email_token_hash TEXT;
+ALTER TABLE customers ADD COLUMN email_token_expires_at TIMESTAMP;
+
+CREATE UNIQUE INDEX customers_email_token_idx ON customers (email_token_hash);
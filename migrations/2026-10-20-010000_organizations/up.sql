@@ -0,0 +1,33 @@
+CREATE TABLE organizations (
+	id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+	_name VARCHAR NOT NULL,
+	personal BOOLEAN NOT NULL DEFAULT false,
+	created_at TIMESTAMP NOT NULL DEFAULT (now() at time zone 'utc')
+);
+
+CREATE TABLE organization_members (
+	org_id UUID NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+	customer_id UUID NOT NULL REFERENCES customers (id) ON DELETE CASCADE,
+	created_at TIMESTAMP NOT NULL DEFAULT (now() at time zone 'utc'),
+	PRIMARY KEY (org_id, customer_id)
+);
+
+CREATE INDEX organization_members_customer_id_idx ON organization_members (customer_id);
+
+CREATE TABLE organization_invitations (
+	id BIGSERIAL PRIMARY KEY,
+	org_id UUID NOT NULL REFERENCES organizations (id) ON DELETE CASCADE,
+	email VARCHAR NOT NULL,
+	token_hash TEXT NOT NULL UNIQUE,
+	invited_by UUID NOT NULL,
+	expires_at TIMESTAMP NOT NULL,
+	accepted_at TIMESTAMP,
+	created_at TIMESTAMP NOT NULL DEFAULT (now() at time zone 'utc')
+);
+
+CREATE INDEX organization_invitations_org_id_idx ON organization_invitations (org_id);
+
+-- Every customer gets a personal organization sharing its id, so the
+-- cid/customer_id of the existing resources now designate that organization.
+INSERT INTO organizations (id, _name, personal) SELECT id, email, true FROM customers;
+INSERT INTO organization_members (org_id, customer_id) SELECT id, id FROM customers;
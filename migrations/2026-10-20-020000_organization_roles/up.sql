@@ -0,0 +1,5 @@
+ALTER TABLE organization_members ADD COLUMN role VARCHAR NOT NULL DEFAULT 'viewer';
+ALTER TABLE organization_invitations ADD COLUMN role VARCHAR NOT NULL DEFAULT 'viewer';
+
+-- Until now the members had full control over the resources of their organization
+UPDATE organization_members SET role = 'owner';
@@ -0,0 +1,11 @@
+CREATE TABLE login_tokens (
+	id BIGSERIAL PRIMARY KEY,
+	cid UUID NOT NULL REFERENCES customers (id) ON DELETE CASCADE,
+	token_hash TEXT NOT NULL UNIQUE,
+	ip TEXT NOT NULL,
+	expires_at TIMESTAMP NOT NULL,
+	used_at TIMESTAMP,
+	created_at TIMESTAMP NOT NULL DEFAULT (now() at time zone 'utc')
+);
+
+CREATE INDEX login_tokens_cid_idx ON login_tokens (cid);
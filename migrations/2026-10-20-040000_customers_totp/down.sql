@@ -0,0 +1,6 @@
+DROP TABLE recovery_codes;
+ALTER TABLE customers DROP COLUMN totp_locked_until;
+ALTER TABLE customers DROP COLUMN totp_failures;
+ALTER TABLE customers DROP COLUMN totp_last_step;
+ALTER TABLE customers DROP COLUMN totp_enabled_at;
+ALTER TABLE customers DROP COLUMN totp_secret;
@@ -0,0 +1,15 @@
+ALTER TABLE customers ADD COLUMN totp_secret TEXT;
+ALTER TABLE customers ADD COLUMN totp_enabled_at TIMESTAMP;
+ALTER TABLE customers ADD COLUMN totp_last_step BIGINT;
+ALTER TABLE customers ADD COLUMN totp_failures INT4 NOT NULL DEFAULT 0;
+ALTER TABLE customers ADD COLUMN totp_locked_until TIMESTAMP;
+
+CREATE TABLE recovery_codes (
+	id BIGSERIAL PRIMARY KEY,
+	cid UUID NOT NULL REFERENCES customers (id) ON DELETE CASCADE,
+	code_hash TEXT NOT NULL,
+	used_at TIMESTAMP,
+	created_at TIMESTAMP NOT NULL DEFAULT (now() at time zone 'utc')
+);
+
+CREATE INDEX recovery_codes_cid_idx ON recovery_codes (cid);
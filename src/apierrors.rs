@@ -10,6 +10,9 @@ pub enum ApiError {
     #[error("template error: failed to build")]
     AskamaError(#[from] askama::Error),
 
+    #[error("csv error: cannot write the records")]
+    CsvError(#[from] csv::Error),
+
     #[error(transparent)]
     DieselError(#[from] diesel::result::Error),
 
@@ -19,6 +22,18 @@ pub enum ApiError {
     #[error(transparent)]
     IoError(#[from] std::io::Error),
 
+    #[error("email error: failed to build")]
+    LettreError(#[from] lettre::error::Error),
+
+    #[error("email error: invalid address")]
+    LettreAddressError(#[from] lettre::address::AddressError),
+
+    #[error(transparent)]
+    LettreFileError(#[from] lettre::transport::file::Error),
+
+    #[error(transparent)]
+    LettreSmtpError(#[from] lettre::transport::smtp::Error),
+
     #[error(transparent)]
     RustTlsError(#[from] rustls::Error),
 
@@ -34,6 +49,9 @@ pub enum ApiError {
     #[error("server error: `{0:?}`")]
     ServerError(Option<String>),
 
+    #[error("http error: `{1}` answered with {0}")]
+    HttpStatusError(u16, String),
+
     #[error("resource not found: `{0:?}`")]
     NotFoundError(Option<String>),
 
@@ -43,9 +61,15 @@ pub enum ApiError {
     #[error("invalid request: `{0:?}`")]
     InvalidRequestError(Option<String>),
 
+    #[error("api key refused: `{0:?}`")]
+    ApiKeyRefusedError(Option<String>),
+
     #[error(transparent)]
     UuidError(#[from] uuid::Error),
 
+    #[error(transparent)]
+    UreqError(#[from] ureq::Error),
+
     #[error("invalid session: `{0:?}`")]
     SessionError(Option<String>),
 
@@ -82,11 +106,11 @@ impl From<ApiError> for actix_web::error::Error {
                 DatabaseErrorKind::UniqueViolation,
                 _,
             )) => actix_web::error::ErrorBadRequest(String::from("the resource already exists")),
-            ApiError::SessionError(x) | ApiError::AuthorizationError(x) => {
-                actix_web::error::ErrorUnauthorized(
-                    x.unwrap_or_else(|| String::from("protected resource, you are not authorized")),
-                )
-            }
+            ApiError::SessionError(x)
+            | ApiError::AuthorizationError(x)
+            | ApiError::ApiKeyRefusedError(x) => actix_web::error::ErrorUnauthorized(
+                x.unwrap_or_else(|| String::from("protected resource, you are not authorized")),
+            ),
             ApiError::ActixSessionError(_) | ApiError::ActixSetSessionError(_) => {
                 actix_web::error::ErrorUnauthorized(String::from(
                     "protected resource, you are not authorized",
@@ -124,7 +148,9 @@ impl IntoResponse for ApiError {
                 DatabaseErrorKind::UniqueViolation,
                 _,
             )) => (StatusCode::BAD_REQUEST, "the resource already exists").into_response(),
-            ApiError::SessionError(x) | ApiError::AuthorizationError(x) => (
+            ApiError::SessionError(x)
+            | ApiError::AuthorizationError(x)
+            | ApiError::ApiKeyRefusedError(x) => (
                 StatusCode::UNAUTHORIZED,
                 x.unwrap_or_else(|| String::from("protected resource, you are not authorized")),
             )
@@ -8,6 +8,7 @@ extern crate diesel;
 
 pub mod apierrors;
 pub mod models;
+pub mod notifications;
 
 use std::fs::File;
 use std::io::BufReader;
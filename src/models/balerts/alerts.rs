@@ -32,12 +32,18 @@ pub struct Alerts {
     pub info: Option<String>,
     // Targeted host
     pub host_uuid: String,
-    // The "owner" of the Alert
+    // The "owner" (organization) of the Alert
     pub cid: Uuid,
     // Targeted hostname
     pub hostname: String,
     // Where SQL condition
     pub where_clause: Option<String>,
+    // Window (in seconds) in which the state changes are counted to detect flapping
+    pub flap_window: Option<i32>,
+    // Number of state changes within the window after which the alert is flapping
+    pub flap_threshold: Option<i32>,
+    // Alerts are soft deleted to keep the context of their incidents
+    pub deleted_at: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(AsChangeset, Deserialize, Serialize, Debug, Default, TS)]
@@ -55,6 +61,8 @@ pub struct AlertsDTOUpdate {
     pub crit: Option<String>,
     pub info: Option<String>,
     pub where_clause: Option<String>,
+    pub flap_window: Option<i32>,
+    pub flap_threshold: Option<i32>,
 }
 
 #[derive(Queryable, QueryableByName, Deserialize, Serialize, Debug, Default, TS)]
@@ -93,4 +101,6 @@ pub struct AlertsDTO {
     pub cid: Uuid,
     pub hostname: String,
     pub where_clause: Option<String>,
+    pub flap_window: Option<i32>,
+    pub flap_threshold: Option<i32>,
 }
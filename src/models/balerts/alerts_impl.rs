@@ -7,7 +7,7 @@ use super::{Alerts, AlertsDTO, AlertsDTOUpdate, HttpAlertsCount, QueryType};
 use crate::apierrors::ApiError;
 use crate::models::balerts::INTERVAL_RGX;
 use crate::models::schema::alerts::dsl::{_name, alerts as dsl_alerts, host_uuid};
-use crate::models::schema::alerts::{cid, id};
+use crate::models::schema::alerts::{active, cid, deleted_at, id};
 use crate::models::{BaseCrud, DtoBase, ExtCrud, DISALLOWED_STATEMENT};
 use crate::ConnType;
 
@@ -78,22 +78,31 @@ impl AlertsDTOTrait for AlertsDTO {
 }
 
 impl Alerts {
-    /// Get all the Alerts (no filter, nothing, just get all)
+    /// Get all the Alerts (no filter, nothing, just get all but the deleted ones)
     /// - conn: the Database connection
     pub fn get_all(conn: &mut ConnType) -> Result<Vec<Self>, ApiError> {
-        Ok(dsl_alerts.load(conn)?)
+        Ok(dsl_alerts.filter(deleted_at.is_null()).load(conn)?)
     }
 
-    /// Is the alert owned by the user
+    /// Has the alert been (soft) deleted by its owner
+    #[inline]
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// Is the alert owned by one of the organizations of the user
     /// - conn: the Database connection
-    /// - cid: the user's UUID
+    /// - orgs: the UUIDs of the user's organizations (see Organizations::get_ids_by_member)
     /// - aid: the id of the alert you want to check
     pub fn exists_by_owner_and_id(
         conn: &mut ConnType,
-        ccid: &Uuid,
+        orgs: &[Uuid],
         aid: i64,
     ) -> Result<bool, ApiError> {
-        Ok(select(exists(dsl_alerts.filter(cid.eq(ccid).and(id.eq(aid))))).get_result(conn)?)
+        Ok(select(exists(dsl_alerts.filter(
+            cid.eq_any(orgs).and(id.eq(aid)).and(deleted_at.is_null()),
+        )))
+        .get_result(conn)?)
     }
 }
 
@@ -118,7 +127,7 @@ impl<'a> BaseCrud<'a> for Alerts {
         page: i64,
     ) -> Result<Self::VecRetType, ApiError> {
         Ok(dsl_alerts
-            .filter(host_uuid.eq(uuid))
+            .filter(host_uuid.eq(uuid).and(deleted_at.is_null()))
             .limit(size)
             .offset(page * size)
             .order_by(_name.asc())
@@ -132,7 +141,10 @@ impl<'a> BaseCrud<'a> for Alerts {
         conn: &mut ConnType,
         target_id: Self::TargetType,
     ) -> Result<Self::RetType, ApiError> {
-        Ok(dsl_alerts.find(target_id).first(conn)?)
+        Ok(dsl_alerts
+            .find(target_id)
+            .filter(deleted_at.is_null())
+            .first(conn)?)
     }
 }
 
@@ -151,7 +163,7 @@ impl<'a> ExtCrud<'a> for Alerts {
 				SELECT
 					active
 				FROM alerts
-				WHERE host_uuid=$1
+				WHERE host_uuid=$1 AND deleted_at IS NULL
 				LIMIT $2
 			)
 			SELECT
@@ -194,9 +206,11 @@ impl<'a> DtoBase<'a> for Alerts {
         target_id: Self::TargetType,
         value: Self::UpdateType,
     ) -> Result<usize, ApiError> {
-        Ok(update(dsl_alerts.filter(id.eq(target_id)))
-            .set(value)
-            .execute(conn)?)
+        Ok(
+            update(dsl_alerts.filter(id.eq(target_id).and(deleted_at.is_null())))
+                .set(value)
+                .execute(conn)?,
+        )
     }
 
     fn update_and_get(
@@ -204,13 +218,23 @@ impl<'a> DtoBase<'a> for Alerts {
         target_id: Self::TargetType,
         value: Self::UpdateType,
     ) -> Result<Self::UpdateReturnType, ApiError> {
-        Ok(update(dsl_alerts.filter(id.eq(target_id)))
-            .set(value)
-            .get_result(conn)?)
+        Ok(
+            update(dsl_alerts.filter(id.eq(target_id).and(deleted_at.is_null())))
+                .set(value)
+                .get_result(conn)?,
+        )
     }
 
+    /// Soft delete the alert, so the incidents it created keep their context
     fn delete(conn: &mut ConnType, target_id: Self::TargetType) -> Result<usize, ApiError> {
-        Ok(delete(dsl_alerts.find(target_id)).execute(conn)?)
+        Ok(
+            update(dsl_alerts.find(target_id).filter(deleted_at.is_null()))
+                .set((
+                    deleted_at.eq(chrono::Utc::now().naive_utc()),
+                    active.eq(false),
+                ))
+                .execute(conn)?,
+        )
     }
 }
 
@@ -340,6 +364,8 @@ impl From<AlertsDTO> for AlertsDTOUpdate {
             crit: Some(alert.crit),
             info: alert.info,
             where_clause: alert.where_clause,
+            flap_window: alert.flap_window,
+            flap_threshold: alert.flap_threshold,
         }
     }
 }
@@ -0,0 +1,60 @@
+use diesel::*;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::models::schema::digest_subscriptions;
+
+/// Periodic summary of the incidents sent by email instead of (or along) the notifications
+#[derive(Identifiable, Queryable, Debug, Serialize, Deserialize, Clone, TS)]
+#[diesel(table_name = digest_subscriptions)]
+#[ts(export)]
+pub struct DigestSubscriptions {
+    #[ts(type = "number")]
+    pub id: i64,
+    // The "owner" (organization) of the subscription
+    pub cid: Uuid,
+    // See DigestPeriod
+    pub period: i32,
+    // Default to the emails of the members of the owner if empty
+    pub recipients: Vec<String>,
+    // Only incidents with a severity >= min_severity are summarized
+    pub min_severity: i32,
+    pub active: bool,
+    // End of the period covered by the last digest sent
+    pub last_sent_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+// ================
+// Insertable model
+// ================
+#[derive(Insertable, Deserialize, Serialize, Debug, TS)]
+#[diesel(table_name = digest_subscriptions)]
+#[ts(export)]
+pub struct DigestSubscriptionsDTO {
+    pub cid: Uuid,
+    pub period: Option<i32>,
+    pub recipients: Option<Vec<String>>,
+    pub min_severity: Option<i32>,
+    pub active: Option<bool>,
+}
+
+/// Using a specific struct for the Update allow us to pass all as None expect the fields we want to update
+#[derive(AsChangeset, Deserialize, Serialize, Debug, Default, TS)]
+#[diesel(table_name = digest_subscriptions)]
+#[ts(export)]
+pub struct DigestSubscriptionsDTOUpdate {
+    pub period: Option<i32>,
+    pub recipients: Option<Vec<String>>,
+    pub min_severity: Option<i32>,
+    pub active: Option<bool>,
+}
+
+/// Period covered by a digest as stored in the period column
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+#[ts(export)]
+pub enum DigestPeriod {
+    Daily = 0,
+    Weekly = 1,
+}
@@ -0,0 +1,277 @@
+use diesel::sql_types::{Bool, Timestamp};
+use diesel::*;
+use uuid::Uuid;
+
+use super::{
+    Alerts, DigestPeriod, DigestSubscriptions, DigestSubscriptionsDTO,
+    DigestSubscriptionsDTOUpdate, Incidents, IncidentsJoined,
+};
+use crate::apierrors::ApiError;
+use crate::models::schema::digest_subscriptions::dsl::{
+    active, cid, digest_subscriptions as dsl_digests, id, last_sent_at,
+};
+use crate::models::schema::{
+    alerts::{self, dsl::id as alid},
+    incidents::{
+        self,
+        dsl::{alerts_id, cid as icid, host_uuid, hostname, resolved_at, severity, started_at},
+    },
+};
+use crate::models::{is_valid_email, BaseCrud, DtoBase};
+use crate::ConnType;
+
+impl DigestPeriod {
+    /// Get the period from the value stored in the database
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(DigestPeriod::Daily),
+            1 => Some(DigestPeriod::Weekly),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DigestPeriod::Daily => "daily",
+            DigestPeriod::Weekly => "weekly",
+        }
+    }
+
+    pub fn duration(&self) -> chrono::Duration {
+        match self {
+            DigestPeriod::Daily => chrono::Duration::days(1),
+            DigestPeriod::Weekly => chrono::Duration::weeks(1),
+        }
+    }
+}
+
+fn validate_period(value: Option<i32>) -> Result<(), ApiError> {
+    match value {
+        Some(value) if DigestPeriod::from_i32(value).is_none() => {
+            Err(ApiError::InvalidRequestError(Some(format!(
+                "digest: period {} is invalid. Valid are: 0 (daily), 1 (weekly).",
+                value
+            ))))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn validate_recipients(recipients: &Option<Vec<String>>) -> Result<(), ApiError> {
+    if let Some(bad) = recipients.iter().flatten().find(|r| !is_valid_email(r)) {
+        return Err(ApiError::InvalidRequestError(Some(format!(
+            "digest: recipient {} is not a valid email address",
+            bad
+        ))));
+    }
+
+    Ok(())
+}
+
+impl DigestSubscriptions {
+    /// Period of the subscription, daily if the stored value is unknown
+    pub fn period(&self) -> DigestPeriod {
+        DigestPeriod::from_i32(self.period).unwrap_or(DigestPeriod::Daily)
+    }
+
+    /// Start of the period covered by the next digest
+    ///
+    /// The first digest covers the time since the creation of the subscription.
+    pub fn period_start(&self) -> chrono::NaiveDateTime {
+        self.last_sent_at.unwrap_or(self.created_at)
+    }
+
+    /// Get the subscriptions having a digest due
+    /// - conn: the Database connection
+    /// - now: the current time
+    ///
+    /// A digest is due one period after the previous one (or the creation).
+    pub fn get_due(conn: &mut ConnType, now: chrono::NaiveDateTime) -> Result<Vec<Self>, ApiError> {
+        let due = dsl::sql::<Bool>(&format!(
+            "COALESCE(last_sent_at, created_at) + CASE period WHEN {} THEN INTERVAL '1 week' \
+            ELSE INTERVAL '1 day' END <= ",
+            DigestPeriod::Weekly as i32
+        ))
+        .bind::<Timestamp, _>(now);
+
+        Ok(dsl_digests
+            .filter(active.eq(true))
+            .filter(due)
+            .order_by(id.asc())
+            .load(conn)?)
+    }
+
+    /// Record that the digest ending at `now` is being sent
+    /// - conn: the Database connection
+    /// - now: the end of the period covered by the digest
+    ///
+    /// Return false if another worker already took the digest,
+    /// in which case it must not be sent again.
+    pub fn mark_sent(
+        &self,
+        conn: &mut ConnType,
+        now: chrono::NaiveDateTime,
+    ) -> Result<bool, ApiError> {
+        let updated = update(
+            dsl_digests.filter(
+                id.eq(self.id)
+                    .and(last_sent_at.is_not_distinct_from(self.last_sent_at)),
+            ),
+        )
+        .set(last_sent_at.eq(now))
+        .execute(conn)?;
+
+        Ok(updated == 1)
+    }
+
+    /// Undo mark_sent after the digest failed to be sent, so it is retried
+    /// - conn: the Database connection
+    /// - now: the value given to mark_sent
+    ///
+    /// Nothing is changed if another digest was recorded meanwhile.
+    pub fn unmark_sent(
+        &self,
+        conn: &mut ConnType,
+        now: chrono::NaiveDateTime,
+    ) -> Result<usize, ApiError> {
+        Ok(
+            update(dsl_digests.filter(id.eq(self.id).and(last_sent_at.eq(now))))
+                .set(last_sent_at.eq(self.last_sent_at))
+                .execute(conn)?,
+        )
+    }
+}
+
+impl Incidents {
+    /// Get the incidents of the user which were active during the period
+    /// - conn: the Database connection
+    /// - uuid: the organization UUID we want the incidents of
+    /// - min_severity: only the incidents with a severity >= min_severity
+    /// - min_date: start of the period
+    /// - max_date: end of the period
+    ///
+    /// Sorted by hostname, then most severe and oldest first.
+    pub fn get_for_digest(
+        conn: &mut ConnType,
+        uuid: &Uuid,
+        min_severity: i32,
+        min_date: chrono::NaiveDateTime,
+        max_date: chrono::NaiveDateTime,
+    ) -> Result<Vec<IncidentsJoined>, ApiError> {
+        Ok(incidents::table
+            .left_join(alerts::table.on(alerts_id.eq(alid)))
+            .filter(
+                icid.eq(uuid)
+                    .and(severity.ge(min_severity))
+                    .and(started_at.lt(max_date))
+                    .and(resolved_at.is_null().or(resolved_at.ge(min_date))),
+            )
+            .order_by((
+                hostname.asc(),
+                host_uuid.asc(),
+                severity.desc(),
+                started_at.asc(),
+            ))
+            .load::<(Self, Option<Alerts>)>(conn)
+            .map(|x| x.into_iter().map(IncidentsJoined::from))?
+            .collect::<Vec<_>>())
+    }
+}
+
+impl<'a> BaseCrud<'a> for DigestSubscriptions {
+    type RetType = DigestSubscriptions;
+
+    type VecRetType = Vec<Self::RetType>;
+
+    type TargetType = i64;
+
+    type UuidType = &'a Uuid;
+
+    /// Get all the digest subscriptions of a user
+    /// - conn: the Database connection
+    /// - uuid: the targeted organization's UUID
+    /// - size: how many elements to return
+    /// - page: pagination :shrug:
+    fn get(
+        conn: &mut ConnType,
+        uuid: Self::UuidType,
+        size: i64,
+        page: i64,
+    ) -> Result<Self::VecRetType, ApiError> {
+        Ok(dsl_digests
+            .filter(cid.eq(uuid))
+            .limit(size)
+            .offset(page * size)
+            .order_by(id.asc())
+            .load(conn)?)
+    }
+
+    /// Get a specific subscription depending on the target_id
+    /// - conn: the Database connection
+    /// - target_id: the targeted subscription's id
+    fn get_specific(
+        conn: &mut ConnType,
+        target_id: Self::TargetType,
+    ) -> Result<Self::RetType, ApiError> {
+        Ok(dsl_digests.find(target_id).first(conn)?)
+    }
+}
+
+impl<'a> DtoBase<'a> for DigestSubscriptions {
+    type GetReturn = DigestSubscriptions;
+
+    type InsertType = &'a DigestSubscriptionsDTO;
+
+    type UpdateType = &'a DigestSubscriptionsDTOUpdate;
+
+    type TargetType = i64;
+
+    type UpdateReturnType = Self::GetReturn;
+
+    fn insert(conn: &mut ConnType, value: Self::InsertType) -> Result<usize, ApiError> {
+        validate_period(value.period)?;
+        validate_recipients(&value.recipients)?;
+
+        Ok(insert_into(dsl_digests).values(value).execute(conn)?)
+    }
+
+    fn insert_and_get(
+        conn: &mut ConnType,
+        value: Self::InsertType,
+    ) -> Result<Self::GetReturn, ApiError> {
+        validate_period(value.period)?;
+        validate_recipients(&value.recipients)?;
+
+        Ok(insert_into(dsl_digests).values(value).get_result(conn)?)
+    }
+
+    fn update(
+        conn: &mut ConnType,
+        target_id: Self::TargetType,
+        value: Self::UpdateType,
+    ) -> Result<usize, ApiError> {
+        validate_period(value.period)?;
+        validate_recipients(&value.recipients)?;
+
+        Ok(update(dsl_digests.filter(id.eq(target_id)))
+            .set(value)
+            .execute(conn)?)
+    }
+
+    fn update_and_get(
+        conn: &mut ConnType,
+        target_id: Self::TargetType,
+        value: Self::UpdateType,
+    ) -> Result<Self::UpdateReturnType, ApiError> {
+        validate_period(value.period)?;
+        validate_recipients(&value.recipients)?;
+
+        Ok(update(dsl_digests.filter(id.eq(target_id)))
+            .set(value)
+            .get_result(conn)?)
+    }
+
+    fn delete(conn: &mut ConnType, target_id: Self::TargetType) -> Result<usize, ApiError> {
+        Ok(delete(dsl_digests.find(target_id)).execute(conn)?)
+    }
+}
@@ -0,0 +1,94 @@
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::{Pg, PgValue},
+    serialize::{self, Output, ToSql},
+    sql_types::Jsonb,
+    *,
+};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::models::schema::escalation_policies;
+
+/// Who gets notified, and when, while an incident is not acknowledged
+///
+/// A policy is attached to an alert, to a host or (if none) is the default
+/// one of its owner. The most specific policy applies to each incident.
+#[derive(Identifiable, Queryable, Debug, Serialize, Deserialize, Clone, TS)]
+#[diesel(table_name = escalation_policies)]
+#[ts(export)]
+pub struct EscalationPolicies {
+    #[ts(type = "number")]
+    pub id: i64,
+    // The "owner" (organization) of the policy
+    pub cid: Uuid,
+    #[diesel(column_name = _name)]
+    pub name: String,
+    pub steps: EscalationSteps,
+    // Only incidents with a severity >= min_severity are escalated
+    pub min_severity: i32,
+    #[ts(type = "number | null")]
+    pub alerts_id: Option<i64>,
+    pub host_uuid: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+// ================
+// Insertable model
+// ================
+#[derive(Insertable, Deserialize, Serialize, Debug, TS)]
+#[diesel(table_name = escalation_policies)]
+#[ts(export)]
+pub struct EscalationPoliciesDTO {
+    pub cid: Uuid,
+    #[diesel(column_name = _name)]
+    pub name: String,
+    pub steps: EscalationSteps,
+    pub min_severity: Option<i32>,
+    #[ts(type = "number | null")]
+    pub alerts_id: Option<i64>,
+    pub host_uuid: Option<String>,
+}
+
+/// Using a specific struct for the Update allow us to pass all as None expect the fields we want to update
+#[derive(AsChangeset, Deserialize, Serialize, Debug, Default, TS)]
+#[diesel(table_name = escalation_policies)]
+#[ts(export)]
+pub struct EscalationPoliciesDTOUpdate {
+    #[diesel(column_name = _name)]
+    pub name: Option<String>,
+    pub steps: Option<EscalationSteps>,
+    pub min_severity: Option<i32>,
+}
+
+/// Ordered steps of a policy, stored as JSON in the steps column
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, TS)]
+#[diesel(sql_type = Jsonb)]
+#[ts(export)]
+pub struct EscalationSteps(pub Vec<EscalationStep>);
+
+/// A tier of the escalation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, TS)]
+#[ts(export)]
+pub struct EscalationStep {
+    // Seconds after the start of the incident at which the step is due
+    pub delay: i32,
+    // Channels notified when the step is due
+    #[ts(type = "Array<number>")]
+    pub channels: Vec<i64>,
+}
+
+impl FromSql<Jsonb, Pg> for EscalationSteps {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = <serde_json::Value as FromSql<Jsonb, Pg>>::from_sql(bytes)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl ToSql<Jsonb, Pg> for EscalationSteps {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        let value = serde_json::to_value(self)?;
+        <serde_json::Value as ToSql<Jsonb, Pg>>::to_sql(&value, &mut out.reborrow())
+    }
+}
@@ -0,0 +1,287 @@
+use std::ops::Range;
+
+use diesel::dsl::exists;
+use diesel::*;
+use uuid::Uuid;
+
+use super::{
+    EscalationPolicies, EscalationPoliciesDTO, EscalationPoliciesDTOUpdate, EscalationSteps,
+    IncidentStatus, Incidents,
+};
+use crate::apierrors::ApiError;
+use crate::models::schema::escalation_policies::dsl::{
+    _name, alerts_id, cid, escalation_policies as dsl_policies, host_uuid, id,
+};
+use crate::models::schema::incidents::dsl::{
+    acknowledged_at, escalation_step, id as incid, incidents as dsl_incidents, status,
+};
+use crate::models::schema::notification_channels::dsl::{
+    cid as chcid, id as chid, notification_channels as dsl_channels,
+};
+use crate::models::{BaseCrud, DtoBase};
+use crate::ConnType;
+
+impl EscalationSteps {
+    /// Assert that the steps are ordered and usable
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if self.0.is_empty() {
+            return Err(ApiError::InvalidRequestError(Some(String::from(
+                "escalation: at least one step is needed",
+            ))));
+        }
+
+        let mut previous = 0;
+        for (idx, step) in self.0.iter().enumerate() {
+            if step.delay < previous {
+                return Err(ApiError::InvalidRequestError(Some(format!(
+                    "escalation: step {} has a delay lower than the previous one",
+                    idx
+                ))));
+            }
+            if step.channels.is_empty() {
+                return Err(ApiError::InvalidRequestError(Some(format!(
+                    "escalation: step {} has no channel",
+                    idx
+                ))));
+            }
+            previous = step.delay;
+        }
+
+        Ok(())
+    }
+
+    /// Get the (deduplicated) channels of the steps
+    pub fn channels(&self, steps: Range<usize>) -> Vec<i64> {
+        let mut channels: Vec<i64> = self.0[steps]
+            .iter()
+            .flat_map(|step| step.channels.iter().copied())
+            .collect();
+        channels.sort_unstable();
+        channels.dedup();
+        channels
+    }
+}
+
+/// Assert that the steps only use channels of the owner
+fn validate_owned_channels(
+    conn: &mut ConnType,
+    owner: &Uuid,
+    steps: &EscalationSteps,
+) -> Result<(), ApiError> {
+    steps.validate()?;
+
+    let channels = steps.channels(0..steps.0.len());
+    let owned: i64 = dsl_channels
+        .filter(chcid.eq(owner).and(chid.eq_any(&channels)))
+        .count()
+        .get_result(conn)?;
+    if owned != channels.len() as i64 {
+        return Err(ApiError::InvalidRequestError(Some(String::from(
+            "escalation: some channels of the steps don't exist",
+        ))));
+    }
+
+    Ok(())
+}
+
+impl EscalationPolicies {
+    /// Is the policy owned by one of the organizations of the user
+    /// - conn: the Database connection
+    /// - orgs: the UUIDs of the user's organizations (see Organizations::get_ids_by_member)
+    /// - pid: the id of the policy you want to check
+    pub fn exists_by_owner_and_id(
+        conn: &mut ConnType,
+        orgs: &[Uuid],
+        pid: i64,
+    ) -> Result<bool, ApiError> {
+        Ok(select(exists(
+            dsl_policies.filter(cid.eq_any(orgs).and(id.eq(pid))),
+        ))
+        .get_result(conn)?)
+    }
+
+    /// Get the policy applying to the incident (if any)
+    /// - conn: the Database connection
+    /// - incident: the incident to escalate
+    ///
+    /// The policy of the alert wins over the one of the host,
+    /// which wins over the default policy of the owner.
+    pub fn get_for_incident(
+        conn: &mut ConnType,
+        incident: &Incidents,
+    ) -> Result<Option<Self>, ApiError> {
+        Ok(dsl_policies
+            .filter(
+                cid.eq(incident.cid).and(
+                    alerts_id
+                        .eq(incident.alerts_id)
+                        .or(host_uuid.eq(&incident.host_uuid))
+                        .or(alerts_id.is_null().and(host_uuid.is_null())),
+                ),
+            )
+            .order_by((alerts_id.asc().nulls_last(), host_uuid.asc().nulls_last()))
+            .first(conn)
+            .optional()?)
+    }
+
+    /// Get the steps of the policy due for the incident at the specific time
+    /// - incident: the incident to escalate
+    /// - now: the current time
+    ///
+    /// Return the range of the steps to notify, which is empty if the incident
+    /// is resolved, acknowledged, not severe enough or has no new step due.
+    /// Each step is due once its delay (since the start of the incident) passed.
+    pub fn due_steps(&self, incident: &Incidents, now: chrono::NaiveDateTime) -> Range<usize> {
+        let done = (incident.escalation_step.max(0) as usize).min(self.steps.0.len());
+        if incident.status != IncidentStatus::Active as i32
+            || incident.acknowledged_at.is_some()
+            || incident.severity < self.min_severity
+        {
+            return done..done;
+        }
+
+        let elapsed = (now - incident.started_at).num_seconds();
+        let due = self
+            .steps
+            .0
+            .iter()
+            .take_while(|step| step.delay as i64 <= elapsed)
+            .count();
+
+        done..due.max(done)
+    }
+}
+
+impl Incidents {
+    /// Get the incidents which could be escalated (active and not acknowledged)
+    /// - conn: the Database connection
+    pub fn get_unacknowledged(conn: &mut ConnType) -> Result<Vec<Self>, ApiError> {
+        Ok(dsl_incidents
+            .filter(
+                status
+                    .eq(IncidentStatus::Active as i32)
+                    .and(acknowledged_at.is_null()),
+            )
+            .load(conn)?)
+    }
+
+    /// Record that the steps up to `to` of the escalation were notified
+    /// - conn: the Database connection
+    /// - from: the escalation_step the caller computed the due steps from
+    /// - to: the new escalation_step
+    ///
+    /// Return false if another evaluator already advanced the escalation,
+    /// in which case the steps must not be notified again.
+    pub fn advance_escalation(
+        &self,
+        conn: &mut ConnType,
+        from: i32,
+        to: i32,
+    ) -> Result<bool, ApiError> {
+        let updated = update(dsl_incidents.filter(incid.eq(self.id).and(escalation_step.eq(from))))
+            .set(escalation_step.eq(to))
+            .execute(conn)?;
+
+        Ok(updated == 1)
+    }
+}
+
+impl<'a> BaseCrud<'a> for EscalationPolicies {
+    type RetType = EscalationPolicies;
+
+    type VecRetType = Vec<Self::RetType>;
+
+    type TargetType = i64;
+
+    type UuidType = &'a Uuid;
+
+    /// Get all the escalation policies defined by a user
+    /// - conn: the Database connection
+    /// - uuid: the targeted organization's UUID
+    /// - size: how many elements to return
+    /// - page: pagination :shrug:
+    fn get(
+        conn: &mut ConnType,
+        uuid: Self::UuidType,
+        size: i64,
+        page: i64,
+    ) -> Result<Self::VecRetType, ApiError> {
+        Ok(dsl_policies
+            .filter(cid.eq(uuid))
+            .limit(size)
+            .offset(page * size)
+            .order_by(_name.asc())
+            .load(conn)?)
+    }
+
+    /// Get a specific policy depending on the target_id
+    /// - conn: the Database connection
+    /// - target_id: the targeted policy's id
+    fn get_specific(
+        conn: &mut ConnType,
+        target_id: Self::TargetType,
+    ) -> Result<Self::RetType, ApiError> {
+        Ok(dsl_policies.find(target_id).first(conn)?)
+    }
+}
+
+impl<'a> DtoBase<'a> for EscalationPolicies {
+    type GetReturn = EscalationPolicies;
+
+    type InsertType = &'a EscalationPoliciesDTO;
+
+    type UpdateType = &'a EscalationPoliciesDTOUpdate;
+
+    type TargetType = i64;
+
+    type UpdateReturnType = Self::GetReturn;
+
+    fn insert(conn: &mut ConnType, value: Self::InsertType) -> Result<usize, ApiError> {
+        validate_owned_channels(conn, &value.cid, &value.steps)?;
+
+        Ok(insert_into(dsl_policies).values(value).execute(conn)?)
+    }
+
+    fn insert_and_get(
+        conn: &mut ConnType,
+        value: Self::InsertType,
+    ) -> Result<Self::GetReturn, ApiError> {
+        validate_owned_channels(conn, &value.cid, &value.steps)?;
+
+        Ok(insert_into(dsl_policies).values(value).get_result(conn)?)
+    }
+
+    fn update(
+        conn: &mut ConnType,
+        target_id: Self::TargetType,
+        value: Self::UpdateType,
+    ) -> Result<usize, ApiError> {
+        if let Some(steps) = &value.steps {
+            let owner = Self::get_specific(conn, target_id)?.cid;
+            validate_owned_channels(conn, &owner, steps)?;
+        }
+
+        Ok(update(dsl_policies.filter(id.eq(target_id)))
+            .set(value)
+            .execute(conn)?)
+    }
+
+    fn update_and_get(
+        conn: &mut ConnType,
+        target_id: Self::TargetType,
+        value: Self::UpdateType,
+    ) -> Result<Self::UpdateReturnType, ApiError> {
+        if let Some(steps) = &value.steps {
+            let owner = Self::get_specific(conn, target_id)?.cid;
+            validate_owned_channels(conn, &owner, steps)?;
+        }
+
+        Ok(update(dsl_policies.filter(id.eq(target_id)))
+            .set(value)
+            .get_result(conn)?)
+    }
+
+    fn delete(conn: &mut ConnType, target_id: Self::TargetType) -> Result<usize, ApiError> {
+        Ok(delete(dsl_policies.find(target_id)).execute(conn)?)
+    }
+}
@@ -1,9 +1,12 @@
-use diesel::{sql_types::BigInt, *};
+use diesel::{
+    sql_types::{BigInt, Date, Float8, Integer, Nullable, Text},
+    *,
+};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 use uuid::Uuid;
 
-use crate::models::schema::incidents;
+use crate::models::{schema::incidents, SortDirection};
 
 use super::Alerts;
 
@@ -26,6 +29,16 @@ pub struct Incidents {
     pub severity: i32,
     pub alerts_id: i64,
     pub cid: Uuid,
+    pub acknowledged_at: Option<chrono::NaiveDateTime>,
+    // Is the alert flapping (many state changes) for this incident
+    pub flapping: bool,
+    // Number of state changes since the incident started flapping
+    pub flap_changes: i32,
+    pub flap_changed_at: Option<chrono::NaiveDateTime>,
+    // Sub-target of the alert (eg: disk, interface), empty if none
+    pub dimension: String,
+    // Number of steps of the escalation policy already notified
+    pub escalation_step: i32,
 }
 
 /// Insertable struct (no id fields => which is auto generated)
@@ -42,6 +55,7 @@ pub struct IncidentsDTO {
     pub severity: i32,
     pub alerts_id: i64,
     pub cid: Uuid,
+    pub dimension: String,
 }
 
 /// Using a specific struct for the Update allow us to pass all as None expect the fields we want to update
@@ -53,6 +67,27 @@ pub struct IncidentsDTOUpdate {
     pub resolved_at: Option<chrono::NaiveDateTime>,
     pub status: Option<i32>,
     pub severity: Option<i32>,
+    pub acknowledged_at: Option<chrono::NaiveDateTime>,
+    pub flapping: Option<bool>,
+    pub flap_changes: Option<i32>,
+    pub flap_changed_at: Option<chrono::NaiveDateTime>,
+    pub escalation_step: Option<i32>,
+}
+
+/// Status of an incident as stored in the status column
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+#[ts(export)]
+pub enum IncidentStatus {
+    Active = 0,
+    Resolved = 1,
+}
+
+/// Severity of an incident as stored in the severity column
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, TS)]
+#[ts(export)]
+pub enum IncidentSeverity {
+    Warning = 0,
+    Critical = 1,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, TS)]
@@ -60,12 +95,16 @@ pub struct IncidentsDTOUpdate {
 pub struct IncidentsJoined {
     #[serde(flatten)]
     pub incident: Incidents,
+    // The alert is kept (soft deleted) even once deleted by the user
     pub alert: Option<Alerts>,
+    // Is the alert still existing (not deleted)
+    pub alert_exists: bool,
 }
 
 impl From<(Incidents, Option<Alerts>)> for IncidentsJoined {
     fn from(v: (Incidents, Option<Alerts>)) -> Self {
         Self {
+            alert_exists: v.1.as_ref().is_some_and(|alert| !alert.is_deleted()),
             incident: v.0,
             alert: v.1,
         }
@@ -80,3 +119,111 @@ pub struct HttpIncidentsCount {
     #[ts(type = "number")]
     pub total: i64,
 }
+
+/// Column used to sort the incidents listing
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum IncidentsSortKey {
+    #[default]
+    UpdatedAt,
+    StartedAt,
+    ResolvedAt,
+    Severity,
+    Status,
+    Hostname,
+}
+
+/// Filters used when listing the incidents, every field is optional
+/// and a missing field means the incidents are not filtered on it.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, TS)]
+#[serde(default)]
+#[ts(export)]
+pub struct IncidentsFilter {
+    pub status: Option<Vec<i32>>,
+    pub severity: Option<Vec<i32>>,
+    pub hosts: Option<Vec<String>>,
+    #[ts(type = "number | null")]
+    pub alerts_id: Option<i64>,
+    pub started_after: Option<chrono::NaiveDateTime>,
+    pub started_before: Option<chrono::NaiveDateTime>,
+    pub resolved_after: Option<chrono::NaiveDateTime>,
+    pub resolved_before: Option<chrono::NaiveDateTime>,
+    // Case insensitive search on the result and the hostname
+    pub search: Option<String>,
+    pub sort: IncidentsSortKey,
+    pub direction: SortDirection,
+}
+
+/// Format of an export of the incidents
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+#[serde(rename_all = "lowercase")]
+#[ts(export)]
+pub enum IncidentsExportFormat {
+    Csv,
+    Ndjson,
+}
+
+/// Dimension on which the incidents statistics are grouped
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+#[serde(rename_all = "lowercase")]
+#[ts(export)]
+pub enum IncidentsStatsGroup {
+    Customer,
+    Host,
+    Alert,
+}
+
+/// Mean time to acknowledge and to resolve (in seconds) of a group of incidents
+#[derive(QueryableByName, Deserialize, Serialize, Debug, Default, TS)]
+#[ts(export)]
+pub struct HttpIncidentsMeanTimes {
+    #[diesel(sql_type = Text)]
+    pub key: String,
+    #[diesel(sql_type = Nullable<Float8>)]
+    pub mtta: Option<f64>,
+    #[diesel(sql_type = Nullable<Float8>)]
+    pub mttr: Option<f64>,
+    #[diesel(sql_type = BigInt)]
+    #[ts(type = "number")]
+    pub total: i64,
+}
+
+/// Number of incidents of a severity over a period
+#[derive(QueryableByName, Deserialize, Serialize, Debug, Default, TS)]
+#[ts(export)]
+pub struct HttpIncidentsSeverityCount {
+    #[diesel(sql_type = Integer)]
+    pub severity: i32,
+    #[diesel(sql_type = BigInt)]
+    #[ts(type = "number")]
+    pub total: i64,
+}
+
+/// Alerts which generated the most incidents over a period
+#[derive(QueryableByName, Deserialize, Serialize, Debug, Default, TS)]
+#[ts(export)]
+pub struct HttpIncidentsTopAlert {
+    #[diesel(sql_type = BigInt)]
+    #[ts(type = "number")]
+    pub alerts_id: i64,
+    // None if the alert does not exists anymore
+    #[diesel(sql_type = Nullable<Text>)]
+    pub name: Option<String>,
+    #[diesel(sql_type = Text)]
+    pub hostname: String,
+    #[diesel(sql_type = BigInt)]
+    #[ts(type = "number")]
+    pub total: i64,
+}
+
+/// Number of incidents started during a day (UTC)
+#[derive(QueryableByName, Deserialize, Serialize, Debug, TS)]
+#[ts(export)]
+pub struct HttpIncidentsDailyCount {
+    #[diesel(sql_type = Date)]
+    pub day: chrono::NaiveDate,
+    #[diesel(sql_type = BigInt)]
+    #[ts(type = "number")]
+    pub total: i64,
+}
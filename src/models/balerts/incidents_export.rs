@@ -0,0 +1,161 @@
+use std::io::Write;
+
+use uuid::Uuid;
+
+use super::{Incidents, IncidentsExportFormat, IncidentsFilter, IncidentsJoined};
+use crate::apierrors::ApiError;
+use crate::ConnType;
+
+/// Number of incidents fetched from the database at once during an export
+const EXPORT_BATCH_SIZE: i64 = 500;
+
+/// Get a batch of incidents after the cursor (see Incidents::get_own_filtered_after)
+type BatchSource =
+    fn(&mut ConnType, &Uuid, &IncidentsFilter, i32, i64) -> Result<Vec<IncidentsJoined>, ApiError>;
+
+const CSV_HEADERS: &[&str] = &[
+    "id",
+    "started_at",
+    "updated_at",
+    "acknowledged_at",
+    "resolved_at",
+    "status",
+    "severity",
+    "flapping",
+    "host_uuid",
+    "hostname",
+    "dimension",
+    "result",
+    "alerts_id",
+    "alert_exists",
+    "alert_name",
+    "alert_table",
+    "alert_lookup",
+    "alert_warn",
+    "alert_crit",
+    "alert_info",
+    "alert_where_clause",
+];
+
+impl IncidentsJoined {
+    /// Flatten the incident and its alert into a CSV record (see CSV_HEADERS)
+    fn to_csv_record(&self) -> Vec<String> {
+        let inc = &self.incident;
+        let fmt_date =
+            |date: Option<chrono::NaiveDateTime>| date.map(|d| d.to_string()).unwrap_or_default();
+        let alert = self.alert.as_ref();
+
+        vec![
+            inc.id.to_string(),
+            inc.started_at.to_string(),
+            inc.updated_at.to_string(),
+            fmt_date(inc.acknowledged_at),
+            fmt_date(inc.resolved_at),
+            inc.status.to_string(),
+            inc.severity.to_string(),
+            inc.flapping.to_string(),
+            inc.host_uuid.to_owned(),
+            inc.hostname.to_owned(),
+            inc.dimension.to_owned(),
+            inc.result.to_owned(),
+            inc.alerts_id.to_string(),
+            self.alert_exists.to_string(),
+            alert.map(|a| a.name.to_owned()).unwrap_or_default(),
+            alert.map(|a| a.table.to_owned()).unwrap_or_default(),
+            alert.map(|a| a.lookup.to_owned()).unwrap_or_default(),
+            alert.map(|a| a.warn.to_owned()).unwrap_or_default(),
+            alert.map(|a| a.crit.to_owned()).unwrap_or_default(),
+            alert.and_then(|a| a.info.to_owned()).unwrap_or_default(),
+            alert
+                .and_then(|a| a.where_clause.to_owned())
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+impl Incidents {
+    /// Stream the incidents of the user, along with their alert, into the writer
+    /// - conn: the Database connection
+    /// - uuid: the organization UUID we want to export the incidents of
+    /// - filter: the filters to apply (sort is ignored, ordered by id)
+    /// - format: the format of the export
+    /// - writer: where the export is written
+    ///
+    /// The archived incidents (see IncidentsRetention) are exported after the
+    /// live ones, so the export is complete whatever the retention policy.
+    /// The incidents are fetched by batch, so the whole export is never held
+    /// in memory. Return the number of exported incidents.
+    pub fn export<W: Write>(
+        conn: &mut ConnType,
+        uuid: &Uuid,
+        filter: &IncidentsFilter,
+        format: IncidentsExportFormat,
+        writer: W,
+    ) -> Result<usize, ApiError> {
+        match format {
+            IncidentsExportFormat::Csv => {
+                let mut wtr = csv::Writer::from_writer(writer);
+                wtr.write_record(CSV_HEADERS)?;
+
+                let total = Self::for_each_batch(conn, uuid, filter, |batch| {
+                    for incident in batch {
+                        wtr.write_record(incident.to_csv_record())?;
+                    }
+                    Ok(())
+                })?;
+
+                wtr.flush()?;
+                Ok(total)
+            }
+            IncidentsExportFormat::Ndjson => {
+                let mut wtr = writer;
+
+                let total = Self::for_each_batch(conn, uuid, filter, |batch| {
+                    for incident in batch {
+                        serde_json::to_writer(&mut wtr, incident)?;
+                        wtr.write_all(b"\n")?;
+                    }
+                    Ok(())
+                })?;
+
+                wtr.flush()?;
+                Ok(total)
+            }
+        }
+    }
+
+    /// Call func on each batch of the live incidents, then of the archived ones
+    fn for_each_batch<F>(
+        conn: &mut ConnType,
+        uuid: &Uuid,
+        filter: &IncidentsFilter,
+        mut func: F,
+    ) -> Result<usize, ApiError>
+    where
+        F: FnMut(&[IncidentsJoined]) -> Result<(), ApiError>,
+    {
+        let sources: [BatchSource; 2] = [
+            Self::get_own_filtered_after,
+            Self::get_archived_filtered_after,
+        ];
+
+        let mut total = 0;
+        for source in sources {
+            let mut after = 0;
+            loop {
+                let batch = source(conn, uuid, filter, after, EXPORT_BATCH_SIZE)?;
+                func(&batch)?;
+
+                total += batch.len();
+                match batch.last() {
+                    Some(last) if (batch.len() as i64) == EXPORT_BATCH_SIZE => {
+                        after = last.incident.id
+                    }
+                    _ => break,
+                }
+            }
+        }
+
+        Ok(total)
+    }
+}
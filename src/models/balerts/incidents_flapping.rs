@@ -0,0 +1,207 @@
+use diesel::sql_types::{BigInt, Text, Timestamp};
+use diesel::*;
+
+use super::{Alerts, HttpIncidentsCount, IncidentStatus, Incidents, IncidentsDTO};
+use crate::apierrors::ApiError;
+use crate::models::schema::incidents::dsl::{
+    acknowledged_at, alerts_id, dimension, escalation_step, flap_changed_at, flap_changes,
+    flapping, incidents as dsl_incidents, resolved_at, result, severity, started_at, status,
+    updated_at,
+};
+use crate::ConnType;
+
+impl Alerts {
+    /// Get the flapping detection parameters (window and threshold) of the alert
+    ///
+    /// Flapping detection is only enabled if both parameters are defined.
+    pub fn flap_config(&self) -> Option<(chrono::Duration, i64)> {
+        match (self.flap_window, self.flap_threshold) {
+            (Some(window), Some(threshold)) if window > 0 && threshold > 0 => {
+                Some((chrono::Duration::seconds(window as i64), threshold as i64))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Incidents {
+    /// Count the state changes of an alert (and dimension) since a specific date
+    /// - conn: the Database connection
+    /// - aid: the targeted alert's id
+    /// - dim: the targeted dimension of the alert
+    /// - since: the start of the window in which to count the changes
+    ///
+    /// The start and the resolution of an incident are each a state change.
+    pub fn count_state_changes(
+        conn: &mut ConnType,
+        aid: i64,
+        dim: &str,
+        since: chrono::NaiveDateTime,
+    ) -> Result<i64, ApiError> {
+        let count: HttpIncidentsCount = sql_query(
+            "
+			SELECT
+				COUNT(*) FILTER (WHERE started_at >= $2)
+					+ COUNT(*) FILTER (WHERE resolved_at >= $2) as total
+			FROM incidents
+			WHERE alerts_id=$1 AND dimension=$3",
+        )
+        .bind::<BigInt, _>(aid)
+        .bind::<Timestamp, _>(since)
+        .bind::<Text, _>(dim)
+        .get_result(conn)?;
+
+        Ok(count.total)
+    }
+
+    /// Open a new incident for the alert, or reopen its last one if the alert is flapping
+    /// - conn: the Database connection
+    /// - alert: the alert which triggered the incident
+    /// - value: the incident to create
+    ///
+    /// When the alert is flapping, the last incident is reopened and flagged as flapping
+    /// instead of creating a new row for each state change. If there's already an
+    /// active incident for the alert and dimension, it's updated instead.
+    pub fn open_or_flap(
+        conn: &mut ConnType,
+        alert: &Alerts,
+        value: &IncidentsDTO,
+    ) -> Result<Self, ApiError> {
+        let (window, threshold) = match alert.flap_config() {
+            Some(config) => config,
+            None => return Self::open_or_update(conn, value),
+        };
+
+        conn.transaction(|conn| {
+            let active: bool = select(dsl::exists(
+                dsl_incidents.filter(
+                    alerts_id
+                        .eq(alert.id)
+                        .and(dimension.eq(&value.dimension))
+                        .and(status.eq(IncidentStatus::Active as i32)),
+                ),
+            ))
+            .get_result(conn)?;
+            if active {
+                return Self::open_or_update(conn, value);
+            }
+
+            let since = value.started_at - window;
+            // +1 for the state change we're about to make
+            if Self::count_state_changes(conn, alert.id, &value.dimension, since)? + 1 < threshold {
+                return Self::open_or_update(conn, value);
+            }
+
+            let last: Option<Self> = dsl_incidents
+                .filter(
+                    alerts_id
+                        .eq(alert.id)
+                        .and(dimension.eq(&value.dimension))
+                        .and(resolved_at.ge(since)),
+                )
+                .order_by(resolved_at.desc())
+                .first(conn)
+                .optional()?;
+
+            match last {
+                Some(last) => Ok(update(dsl_incidents.find(last.id))
+                    .set((
+                        result.eq(&value.result),
+                        updated_at.eq(value.updated_at),
+                        resolved_at.eq(None::<chrono::NaiveDateTime>),
+                        // A reopened incident has to be acknowledged again,
+                        // and is escalated from the start since it reopened
+                        acknowledged_at.eq(None::<chrono::NaiveDateTime>),
+                        escalation_step.eq(0),
+                        started_at.eq(value.started_at),
+                        status.eq(IncidentStatus::Active as i32),
+                        severity.eq(value.severity),
+                        flapping.eq(true),
+                        // The firing change which reopened the incident
+                        flap_changes.eq(1),
+                        flap_changed_at.eq(value.started_at),
+                    ))
+                    .get_result(conn)?),
+                None => Self::open_or_update(conn, value),
+            }
+        })
+    }
+
+    /// Is the alert currently firing for this flapping incident
+    ///
+    /// A flapping incident is reopened by a firing change and then alternate
+    /// between ok and firing, so an odd number of changes means firing.
+    #[inline]
+    pub fn is_flap_firing(&self) -> bool {
+        self.flap_changes % 2 == 1
+    }
+
+    /// Register that the alert of the incident is firing (again)
+    /// - conn: the Database connection
+    /// - when: the time at which the alert fired
+    ///
+    /// Does nothing if the incident is not flapping or already firing.
+    pub fn record_firing(
+        &self,
+        conn: &mut ConnType,
+        when: chrono::NaiveDateTime,
+    ) -> Result<Self, ApiError> {
+        if !self.flapping || self.is_flap_firing() {
+            return Ok(self.clone());
+        }
+
+        Ok(update(dsl_incidents.find(self.id))
+            .set((
+                updated_at.eq(when),
+                flap_changes.eq(flap_changes + 1),
+                flap_changed_at.eq(when),
+            ))
+            .get_result(conn)?)
+    }
+
+    /// Resolve the incident, unless it's flapping and the alert is not stable yet
+    /// - conn: the Database connection
+    /// - alert: the alert which triggered the incident
+    /// - when: the time at which the alert stopped firing
+    ///
+    /// A flapping incident is only resolved once the alert didn't change
+    /// state for a whole flapping window. Until then it's kept open and
+    /// the returned incident has no resolved_at.
+    pub fn resolve_or_hold(
+        &self,
+        conn: &mut ConnType,
+        alert: &Alerts,
+        when: chrono::NaiveDateTime,
+    ) -> Result<Self, ApiError> {
+        let window = match alert.flap_config() {
+            Some((window, _)) if self.flapping => window,
+            _ => return self.resolve(conn, when),
+        };
+
+        if self.is_flap_firing() {
+            return Ok(update(dsl_incidents.find(self.id))
+                .set((
+                    updated_at.eq(when),
+                    flap_changes.eq(flap_changes + 1),
+                    flap_changed_at.eq(when),
+                ))
+                .get_result(conn)?);
+        }
+
+        match self.flap_changed_at {
+            Some(changed_at) if changed_at + window > when => Ok(self.clone()),
+            _ => self.resolve(conn, when),
+        }
+    }
+
+    fn resolve(&self, conn: &mut ConnType, when: chrono::NaiveDateTime) -> Result<Self, ApiError> {
+        Ok(update(dsl_incidents.find(self.id))
+            .set((
+                updated_at.eq(when),
+                resolved_at.eq(when),
+                status.eq(IncidentStatus::Resolved as i32),
+                flapping.eq(false),
+            ))
+            .get_result(conn)?)
+    }
+}
@@ -1,37 +1,162 @@
-use diesel::sql_types::{BigInt, Text};
+use diesel::sql_types::{BigInt, Bool, Text};
+use diesel::upsert::excluded;
 use diesel::*;
 use uuid::Uuid;
 
 use super::{
-    Alerts, HttpIncidentsCount, Incidents, IncidentsDTO, IncidentsDTOUpdate, IncidentsJoined,
+    Alerts, HttpIncidentsCount, IncidentSeverity, IncidentStatus, Incidents, IncidentsDTO,
+    IncidentsDTOUpdate, IncidentsFilter, IncidentsJoined, IncidentsSortKey,
 };
 use crate::apierrors::ApiError;
 use crate::models::schema::{
     alerts::{self, dsl::id as alid},
     incidents::{
         self,
-        dsl::{alerts_id, cid, host_uuid, id, incidents as dsl_incidents, status, updated_at},
+        dsl::{
+            acknowledged_at, alerts_id, cid, dimension, host_uuid, hostname, id,
+            incidents as dsl_incidents, resolved_at, result, severity, started_at, status,
+            updated_at,
+        },
     },
 };
-use crate::models::{BaseCrud, DtoBase, ExtCrud};
+use crate::models::{BaseCrud, DtoBase, ExtCrud, SortDirection};
 use crate::ConnType;
 
+/// Apply the filters of an IncidentsFilter to a boxed query on the incidents
+///
+/// Shared between the listing and the count so they always match.
+macro_rules! filter_incidents {
+    ($query:expr, $uuid:expr, $filter:expr) => {{
+        let filter: &IncidentsFilter = $filter;
+        let mut query = $query.filter(cid.eq($uuid));
+
+        if let Some(values) = &filter.status {
+            query = query.filter(status.eq_any(values));
+        }
+        if let Some(values) = &filter.severity {
+            query = query.filter(severity.eq_any(values));
+        }
+        if let Some(values) = &filter.hosts {
+            query = query.filter(host_uuid.eq_any(values));
+        }
+        if let Some(aid) = filter.alerts_id {
+            query = query.filter(alerts_id.eq(aid));
+        }
+        if let Some(date) = filter.started_after {
+            query = query.filter(started_at.ge(date));
+        }
+        if let Some(date) = filter.started_before {
+            query = query.filter(started_at.le(date));
+        }
+        if let Some(date) = filter.resolved_after {
+            query = query.filter(resolved_at.ge(date));
+        }
+        if let Some(date) = filter.resolved_before {
+            query = query.filter(resolved_at.le(date));
+        }
+        if let Some(search) = &filter.search {
+            let pattern = format!("%{}%", escape_like(search));
+            query = query.filter(result.ilike(pattern.clone()).or(hostname.ilike(pattern)));
+        }
+
+        query
+    }};
+}
+
+/// Escape the LIKE wildcards of a user provided search string
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+impl IncidentSeverity {
+    /// Get the severity from the value stored in the database
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(IncidentSeverity::Warning),
+            1 => Some(IncidentSeverity::Critical),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IncidentSeverity::Warning => "warning",
+            IncidentSeverity::Critical => "critical",
+        }
+    }
+}
+
 impl Incidents {
-    /// Get the active incident for the specific alert (if any)
+    /// Get the active incident for the specific alert and dimension (if any)
     /// - conn: the Database connection
     /// - aid: the targeted alert's id
+    /// - dim: the targeted dimension of the alert
     ///
-    /// In theory there should at most be one active incidents
-    /// per alert per host. If there's more than one it's not handled.
-    pub fn find_active(conn: &mut ConnType, aid: i64) -> Result<Self, ApiError> {
+    /// There's at most one active incident per alert per dimension,
+    /// which is enforced by a partial unique index on the table.
+    pub fn find_active(conn: &mut ConnType, aid: i64, dim: &str) -> Result<Self, ApiError> {
         Ok(dsl_incidents
-            .filter(alerts_id.eq(aid).and(status.eq(0)))
+            .filter(
+                alerts_id
+                    .eq(aid)
+                    .and(dimension.eq(dim))
+                    .and(status.eq(IncidentStatus::Active as i32)),
+            )
             .first(conn)?)
     }
 
-    /// Get the incidents of that particular Uuid (user)
+    /// Open a new incident or update the active one of the same alert and dimension
     /// - conn: the Database connection
-    /// - uuid: the user UUID we want the incidents of
+    /// - value: the incident to open (or the new values of the active one)
+    ///
+    /// This is atomic, concurrent workers can't create duplicated active incidents.
+    /// The duplicates created before the unique index are merged by its migration.
+    pub fn open_or_update(conn: &mut ConnType, value: &IncidentsDTO) -> Result<Self, ApiError> {
+        Ok(insert_into(dsl_incidents)
+            .values(value)
+            .on_conflict((alerts_id, dimension))
+            // Must be a literal (not a bind) for Postgres to infer the partial index
+            .filter_target(dsl::sql::<Bool>(&format!(
+                "status = {}",
+                IncidentStatus::Active as i32
+            )))
+            .do_update()
+            .set((
+                result.eq(excluded(result)),
+                updated_at.eq(excluded(updated_at)),
+                severity.eq(excluded(severity)),
+            ))
+            .get_result(conn)?)
+    }
+
+    /// Mark the incident as acknowledged (if it's not already)
+    /// - conn: the Database connection
+    /// - target_id: the targeted incident's id
+    ///
+    /// The first acknowledgement is the one kept, as it's the one
+    /// used to compute the mean time to acknowledge.
+    pub fn acknowledge(conn: &mut ConnType, target_id: i32) -> Result<Self, ApiError> {
+        let acked = update(
+            dsl_incidents
+                .find(target_id)
+                .filter(acknowledged_at.is_null()),
+        )
+        .set(acknowledged_at.eq(chrono::Utc::now().naive_utc()))
+        .get_result(conn)
+        .optional()?;
+
+        match acked {
+            Some(incident) => Ok(incident),
+            None => Ok(dsl_incidents.find(target_id).first(conn)?),
+        }
+    }
+
+    /// Get the incidents of that particular Uuid (organization)
+    /// - conn: the Database connection
+    /// - uuid: the organization UUID we want the incidents of
     /// - size: how many elements to return
     /// - page: pagination :shrug:
     pub fn get_owned(
@@ -48,12 +173,12 @@ impl Incidents {
             .load(conn)?)
     }
 
-    /// Get the incidents of that particular Uuid (user) linked with the alerts
+    /// Get the incidents of that particular Uuid (organization) linked with the alerts
     ///
-    /// Note: if the alerts does not exists anymore, it won't return the incident field neither
-    /// TOOD - Take care of this case
+    /// Note: deleted alerts are soft deleted and thus still returned (with alert_exists
+    /// set to false). Only incidents of alerts deleted before that won't have the alert.
     /// - conn: the Database connection
-    /// - uuid: the user UUID we want the incidents of
+    /// - uuid: the organization UUID we want the incidents of
     /// - size: how many elements to return
     /// - page: pagination :shrug:
     pub fn get_own_joined(
@@ -62,15 +187,18 @@ impl Incidents {
         size: i64,
         page: i64,
     ) -> Result<Vec<IncidentsJoined>, ApiError> {
+        Self::get_own_filtered(conn, uuid, &IncidentsFilter::default(), size, page)
+    }
+
+    /// Get a specific incident along with its alert
+    /// - conn: the Database connection
+    /// - target_id: the targeted incident's id
+    pub fn get_joined(conn: &mut ConnType, target_id: i32) -> Result<IncidentsJoined, ApiError> {
         Ok(incidents::table
-            .filter(cid.eq(uuid))
-            .limit(size)
-            .offset(page * size)
-            .order_by(updated_at.desc())
             .left_join(alerts::table.on(alerts_id.eq(alid)))
-            .load::<(Self, Option<Alerts>)>(conn)
-            .map(|x| x.into_iter().map(IncidentsJoined::from))?
-            .collect::<Vec<_>>())
+            .filter(id.eq(target_id))
+            .first::<(Self, Option<Alerts>)>(conn)
+            .map(IncidentsJoined::from)?)
     }
 
     /// Same as get_own_joined but with specific host targeted
@@ -81,17 +209,179 @@ impl Incidents {
         size: i64,
         page: i64,
     ) -> Result<Vec<IncidentsJoined>, ApiError> {
-        Ok(incidents::table
-            .filter(cid.eq(uuid))
-            .filter(host_uuid.eq(huuid))
+        let filter = IncidentsFilter {
+            hosts: Some(vec![huuid.to_owned()]),
+            ..Default::default()
+        };
+
+        Self::get_own_filtered(conn, uuid, &filter, size, page)
+    }
+
+    /// Same as get_own_joined but filtered and sorted using the IncidentsFilter
+    /// - conn: the Database connection
+    /// - uuid: the organization UUID we want the incidents of
+    /// - filter: the filters and sort order to apply
+    /// - size: how many elements to return
+    /// - page: pagination :shrug:
+    pub fn get_own_filtered(
+        conn: &mut ConnType,
+        uuid: &Uuid,
+        filter: &IncidentsFilter,
+        size: i64,
+        page: i64,
+    ) -> Result<Vec<IncidentsJoined>, ApiError> {
+        let query = filter_incidents!(
+            incidents::table
+                .left_join(alerts::table.on(alerts_id.eq(alid)))
+                .into_boxed(),
+            uuid,
+            filter
+        );
+
+        let query = match (filter.sort, filter.direction) {
+            (IncidentsSortKey::UpdatedAt, SortDirection::Asc) => query.order_by(updated_at.asc()),
+            (IncidentsSortKey::UpdatedAt, SortDirection::Desc) => query.order_by(updated_at.desc()),
+            (IncidentsSortKey::StartedAt, SortDirection::Asc) => query.order_by(started_at.asc()),
+            (IncidentsSortKey::StartedAt, SortDirection::Desc) => query.order_by(started_at.desc()),
+            (IncidentsSortKey::ResolvedAt, SortDirection::Asc) => {
+                query.order_by(resolved_at.asc().nulls_last())
+            }
+            (IncidentsSortKey::ResolvedAt, SortDirection::Desc) => {
+                query.order_by(resolved_at.desc().nulls_last())
+            }
+            (IncidentsSortKey::Severity, SortDirection::Asc) => query.order_by(severity.asc()),
+            (IncidentsSortKey::Severity, SortDirection::Desc) => query.order_by(severity.desc()),
+            (IncidentsSortKey::Status, SortDirection::Asc) => query.order_by(status.asc()),
+            (IncidentsSortKey::Status, SortDirection::Desc) => query.order_by(status.desc()),
+            (IncidentsSortKey::Hostname, SortDirection::Asc) => query.order_by(hostname.asc()),
+            (IncidentsSortKey::Hostname, SortDirection::Desc) => query.order_by(hostname.desc()),
+        };
+
+        Ok(query
+            // Keep the pagination stable for equal sort values
+            .then_order_by(id.desc())
             .limit(size)
             .offset(page * size)
-            .order_by(updated_at.desc())
-            .left_join(alerts::table.on(alerts_id.eq(alid)))
             .load::<(Self, Option<Alerts>)>(conn)
             .map(|x| x.into_iter().map(IncidentsJoined::from))?
             .collect::<Vec<_>>())
     }
+
+    /// Get the incidents of the user matching the IncidentsFilter after the cursor
+    /// - conn: the Database connection
+    /// - uuid: the organization UUID we want the incidents of
+    /// - filter: the filters to apply (sort is ignored, ordered by id)
+    /// - after: only the incidents with an id greater than this one
+    /// - size: how many elements to return
+    ///
+    /// Keyset pagination, which stays fast on the last pages and does not
+    /// skip nor repeat incidents when some are added or deleted meanwhile.
+    pub fn get_own_filtered_after(
+        conn: &mut ConnType,
+        uuid: &Uuid,
+        filter: &IncidentsFilter,
+        after: i32,
+        size: i64,
+    ) -> Result<Vec<IncidentsJoined>, ApiError> {
+        let query = filter_incidents!(
+            incidents::table
+                .left_join(alerts::table.on(alerts_id.eq(alid)))
+                .into_boxed(),
+            uuid,
+            filter
+        );
+
+        Ok(query
+            .filter(id.gt(after))
+            .order_by(id.asc())
+            .limit(size)
+            .load::<(Self, Option<Alerts>)>(conn)
+            .map(|x| x.into_iter().map(IncidentsJoined::from))?
+            .collect::<Vec<_>>())
+    }
+
+    /// Get the archived incidents of the user matching the IncidentsFilter after the cursor
+    /// - conn: the Database connection
+    /// - uuid: the organization UUID we want the incidents of
+    /// - filter: the filters to apply (sort is ignored, ordered by id)
+    /// - after: only the incidents with an id greater than this one
+    /// - size: how many elements to return
+    ///
+    /// Same as get_own_filtered_after, for the incidents moved to the
+    /// incidents_archive by their retention policy (see IncidentsRetention).
+    pub fn get_archived_filtered_after(
+        conn: &mut ConnType,
+        uuid: &Uuid,
+        filter: &IncidentsFilter,
+        after: i32,
+        size: i64,
+    ) -> Result<Vec<IncidentsJoined>, ApiError> {
+        // Shadow the incidents columns, so filter_incidents! targets the archive
+        use crate::models::schema::incidents_archive::dsl::{
+            acknowledged_at, alerts_id, cid, dimension, escalation_step, flap_changed_at,
+            flap_changes, flapping, host_uuid, hostname, id, incidents_archive, resolved_at,
+            result, severity, started_at, status, updated_at,
+        };
+
+        let query = filter_incidents!(
+            incidents_archive
+                .left_join(alerts::table.on(alerts_id.eq(alid)))
+                .into_boxed(),
+            uuid,
+            filter
+        );
+
+        Ok(query
+            .filter(id.gt(after))
+            .order_by(id.asc())
+            .limit(size)
+            // Same order as the Incidents fields (archived_at is not part of it)
+            .select((
+                (
+                    id,
+                    result,
+                    started_at,
+                    updated_at,
+                    resolved_at,
+                    host_uuid,
+                    hostname,
+                    status,
+                    severity,
+                    alerts_id,
+                    cid,
+                    acknowledged_at,
+                    flapping,
+                    flap_changes,
+                    flap_changed_at,
+                    dimension,
+                    escalation_step,
+                ),
+                alerts::all_columns.nullable(),
+            ))
+            .load::<(Self, Option<Alerts>)>(conn)
+            .map(|x| x.into_iter().map(IncidentsJoined::from))?
+            .collect::<Vec<_>>())
+    }
+
+    /// Count the incidents of the user matching the IncidentsFilter
+    /// - conn: the Database connection
+    /// - uuid: the organization UUID we want to count the incidents of
+    /// - filter: the filters to apply (sort is ignored)
+    pub fn count_own_filtered(
+        conn: &mut ConnType,
+        uuid: &Uuid,
+        filter: &IncidentsFilter,
+    ) -> Result<HttpIncidentsCount, ApiError> {
+        let query = filter_incidents!(
+            dsl_incidents.select(dsl::count_star()).into_boxed(),
+            uuid,
+            filter
+        );
+
+        Ok(HttpIncidentsCount {
+            total: query.get_result(conn)?,
+        })
+    }
 }
 
 impl<'a> BaseCrud<'a> for Incidents {
@@ -222,6 +512,7 @@ impl From<Incidents> for IncidentsDTO {
             severity: incident.severity,
             alerts_id: incident.alerts_id,
             cid: incident.cid,
+            dimension: incident.dimension,
         }
     }
 }
@@ -234,6 +525,11 @@ impl From<Incidents> for IncidentsDTOUpdate {
             resolved_at: incident.resolved_at,
             status: Some(incident.status),
             severity: Some(incident.severity),
+            acknowledged_at: incident.acknowledged_at,
+            flapping: Some(incident.flapping),
+            flap_changes: Some(incident.flap_changes),
+            flap_changed_at: incident.flap_changed_at,
+            escalation_step: Some(incident.escalation_step),
         }
     }
 }
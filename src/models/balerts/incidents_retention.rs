@@ -0,0 +1,19 @@
+use diesel::*;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::models::schema::incidents_retention;
+
+/// Retention policy of the resolved incidents of a customer
+#[derive(Identifiable, Queryable, Insertable, AsChangeset, Debug, Serialize, Deserialize, TS)]
+#[diesel(table_name = incidents_retention)]
+#[diesel(primary_key(cid))]
+#[ts(export)]
+pub struct IncidentsRetention {
+    pub cid: Uuid,
+    // Number of days after their resolution the incidents are kept
+    pub days: i32,
+    // Move the incidents to the archive table instead of deleting them
+    pub archive: bool,
+}
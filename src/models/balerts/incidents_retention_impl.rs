@@ -0,0 +1,131 @@
+use diesel::sql_types::Timestamp;
+use diesel::*;
+use uuid::Uuid;
+
+use super::{IncidentStatus, IncidentsRetention};
+use crate::apierrors::ApiError;
+use crate::models::schema::incidents_retention::dsl::{cid, incidents_retention as dsl_retention};
+use crate::models::schema::{incidents, incidents_archive};
+use crate::ConnType;
+
+impl IncidentsRetention {
+    /// Get the retention policy of the organization
+    /// - conn: the Database connection
+    /// - uuid: the organization's UUID
+    pub fn get_by_owner(conn: &mut ConnType, uuid: &Uuid) -> Result<Self, ApiError> {
+        Ok(dsl_retention.find(uuid).first(conn)?)
+    }
+
+    /// Create or replace the retention policy of the user
+    /// - conn: the Database connection
+    /// - value: the new retention policy
+    pub fn upsert(conn: &mut ConnType, value: &Self) -> Result<Self, ApiError> {
+        if value.days < 1 {
+            return Err(ApiError::InvalidRequestError(Some(String::from(
+                "retention: days must be greater than 0",
+            ))));
+        }
+
+        Ok(insert_into(dsl_retention)
+            .values(value)
+            .on_conflict(cid)
+            .do_update()
+            .set(value)
+            .get_result(conn)?)
+    }
+
+    /// Delete the retention policy of the user (incidents are then kept forever)
+    /// - conn: the Database connection
+    /// - uuid: the user's UUID
+    pub fn delete_by_owner(conn: &mut ConnType, uuid: &Uuid) -> Result<usize, ApiError> {
+        Ok(delete(dsl_retention.find(uuid)).execute(conn)?)
+    }
+
+    /// Archive or delete the resolved incidents older than the retention
+    /// - conn: the Database connection
+    ///
+    /// Run in a transaction (a savepoint if already in one), as the archived
+    /// incidents are copied then deleted. Return the number of incidents archived (or deleted).
+    pub fn apply(&self, conn: &mut ConnType) -> Result<usize, ApiError> {
+        conn.transaction(|conn| self.apply_inner(conn))
+    }
+
+    fn apply_inner(&self, conn: &mut ConnType) -> Result<usize, ApiError> {
+        let now = chrono::Utc::now().naive_utc();
+        let until = now - chrono::Duration::days(self.days as i64);
+
+        let expired = incidents::table
+            .filter(incidents::cid.eq(self.cid))
+            .filter(incidents::status.eq(IncidentStatus::Resolved as i32))
+            .filter(incidents::resolved_at.lt(until));
+
+        if !self.archive {
+            return Ok(delete(expired).execute(conn)?);
+        }
+
+        let archived: Vec<i32> = insert_into(incidents_archive::table)
+            .values(expired.select((
+                incidents::id,
+                incidents::result,
+                incidents::started_at,
+                incidents::updated_at,
+                incidents::resolved_at,
+                incidents::host_uuid,
+                incidents::hostname,
+                incidents::status,
+                incidents::severity,
+                incidents::alerts_id,
+                incidents::cid,
+                incidents::acknowledged_at,
+                incidents::flapping,
+                incidents::flap_changes,
+                incidents::flap_changed_at,
+                incidents::dimension,
+                incidents::escalation_step,
+                now.into_sql::<Timestamp>(),
+            )))
+            .into_columns((
+                incidents_archive::id,
+                incidents_archive::result,
+                incidents_archive::started_at,
+                incidents_archive::updated_at,
+                incidents_archive::resolved_at,
+                incidents_archive::host_uuid,
+                incidents_archive::hostname,
+                incidents_archive::status,
+                incidents_archive::severity,
+                incidents_archive::alerts_id,
+                incidents_archive::cid,
+                incidents_archive::acknowledged_at,
+                incidents_archive::flapping,
+                incidents_archive::flap_changes,
+                incidents_archive::flap_changed_at,
+                incidents_archive::dimension,
+                incidents_archive::escalation_step,
+                incidents_archive::archived_at,
+            ))
+            .returning(incidents_archive::id)
+            .get_results(conn)?;
+
+        Ok(delete(incidents::table.filter(incidents::id.eq_any(&archived))).execute(conn)?)
+    }
+
+    /// Apply the retention policy of every users
+    /// - conn: the Database connection
+    ///
+    /// Each policy is applied in its own transaction, so a failing
+    /// one does not prevent the others from being applied.
+    pub fn apply_all(conn: &mut ConnType) -> Result<usize, ApiError> {
+        let policies: Vec<Self> = dsl_retention.load(conn)?;
+
+        let mut total = 0;
+        for policy in policies {
+            match policy.apply(conn) {
+                Ok(count) => total += count,
+                Err(err) => error!("retention: cannot apply for {}: {}", policy.cid, err),
+            }
+        }
+
+        Ok(total)
+    }
+}
@@ -0,0 +1,151 @@
+use diesel::sql_types::{BigInt, Timestamp, Uuid as DieselUuid};
+use diesel::*;
+use uuid::Uuid;
+
+use super::{
+    HttpIncidentsDailyCount, HttpIncidentsMeanTimes, HttpIncidentsSeverityCount,
+    HttpIncidentsTopAlert, Incidents, IncidentsStatsGroup,
+};
+use crate::apierrors::ApiError;
+use crate::ConnType;
+
+impl IncidentsStatsGroup {
+    /// Column (casted as text) used to group the incidents on
+    fn column(&self) -> &'static str {
+        match self {
+            IncidentsStatsGroup::Customer => "cid::text",
+            IncidentsStatsGroup::Host => "host_uuid",
+            IncidentsStatsGroup::Alert => "alerts_id::text",
+        }
+    }
+}
+
+impl Incidents {
+    /// Get the mean time to acknowledge and to resolve of the user's incidents
+    /// - conn: the Database connection
+    /// - uuid: the organization UUID we want the stats of
+    /// - group: the dimension used to group the incidents
+    /// - min_date: incidents started after this date
+    /// - max_date: incidents started before this date
+    ///
+    /// Incidents which are not acknowledged (or resolved) yet are
+    /// ignored in the computation of the mtta (or mttr).
+    pub fn mean_times(
+        conn: &mut ConnType,
+        uuid: &Uuid,
+        group: IncidentsStatsGroup,
+        min_date: chrono::NaiveDateTime,
+        max_date: chrono::NaiveDateTime,
+    ) -> Result<Vec<HttpIncidentsMeanTimes>, ApiError> {
+        Ok(sql_query(format!(
+            "
+			SELECT
+				{} as key,
+				AVG(EXTRACT(EPOCH FROM (acknowledged_at - started_at)))::float8 as mtta,
+				AVG(EXTRACT(EPOCH FROM (resolved_at - started_at)))::float8 as mttr,
+				COUNT(*) as total
+			FROM incidents
+			WHERE cid=$1 AND started_at BETWEEN $2 AND $3
+			GROUP BY key
+			ORDER BY key",
+            group.column()
+        ))
+        .bind::<DieselUuid, _>(uuid)
+        .bind::<Timestamp, _>(min_date)
+        .bind::<Timestamp, _>(max_date)
+        .load(conn)?)
+    }
+
+    /// Get the number of incidents of the user for each severity
+    /// - conn: the Database connection
+    /// - uuid: the organization UUID we want the stats of
+    /// - min_date: incidents started after this date
+    /// - max_date: incidents started before this date
+    pub fn count_by_severity(
+        conn: &mut ConnType,
+        uuid: &Uuid,
+        min_date: chrono::NaiveDateTime,
+        max_date: chrono::NaiveDateTime,
+    ) -> Result<Vec<HttpIncidentsSeverityCount>, ApiError> {
+        Ok(sql_query(
+            "
+			SELECT
+				severity,
+				COUNT(*) as total
+			FROM incidents
+			WHERE cid=$1 AND started_at BETWEEN $2 AND $3
+			GROUP BY severity
+			ORDER BY severity",
+        )
+        .bind::<DieselUuid, _>(uuid)
+        .bind::<Timestamp, _>(min_date)
+        .bind::<Timestamp, _>(max_date)
+        .load(conn)?)
+    }
+
+    /// Get the alerts of the user which generated the most incidents
+    /// - conn: the Database connection
+    /// - uuid: the organization UUID we want the stats of
+    /// - min_date: incidents started after this date
+    /// - max_date: incidents started before this date
+    /// - size: how many alerts to return
+    pub fn top_alerts(
+        conn: &mut ConnType,
+        uuid: &Uuid,
+        min_date: chrono::NaiveDateTime,
+        max_date: chrono::NaiveDateTime,
+        size: i64,
+    ) -> Result<Vec<HttpIncidentsTopAlert>, ApiError> {
+        Ok(sql_query(
+            "
+			SELECT
+				i.alerts_id,
+				a._name as name,
+				i.hostname,
+				COUNT(*) as total
+			FROM incidents i
+			LEFT JOIN alerts a ON a.id = i.alerts_id
+			WHERE i.cid=$1 AND i.started_at BETWEEN $2 AND $3
+			GROUP BY i.alerts_id, a._name, i.hostname
+			ORDER BY total DESC, i.alerts_id
+			LIMIT $4",
+        )
+        .bind::<DieselUuid, _>(uuid)
+        .bind::<Timestamp, _>(min_date)
+        .bind::<Timestamp, _>(max_date)
+        .bind::<BigInt, _>(size)
+        .load(conn)?)
+    }
+
+    /// Get the number of incidents started each day
+    /// - conn: the Database connection
+    /// - uuid: the organization UUID we want the stats of
+    /// - min_date: first day of the histogram
+    /// - max_date: last day of the histogram
+    ///
+    /// Days without any incident are returned with a total of 0.
+    /// The days are the UTC ones, as the dates are stored in UTC.
+    pub fn daily_histogram(
+        conn: &mut ConnType,
+        uuid: &Uuid,
+        min_date: chrono::NaiveDateTime,
+        max_date: chrono::NaiveDateTime,
+    ) -> Result<Vec<HttpIncidentsDailyCount>, ApiError> {
+        Ok(sql_query(
+            "
+			SELECT
+				d::date as day,
+				COUNT(i.id) as total
+			FROM generate_series($2::date::timestamp, $3::date::timestamp, INTERVAL '1 day') d
+			LEFT JOIN incidents i ON i.cid=$1
+				AND i.started_at >= d
+				AND i.started_at < d + INTERVAL '1 day'
+			GROUP BY d
+			ORDER BY d",
+        )
+        .bind::<DieselUuid, _>(uuid)
+        .bind::<Timestamp, _>(min_date)
+        .bind::<Timestamp, _>(max_date)
+        .load(conn)?)
+    }
+}
@@ -8,10 +8,37 @@ pub use alerts::*;
 pub use alerts_impl::*;
 pub use alerts_querying::*;
 
+mod digest_subscriptions;
+mod digest_subscriptions_impl;
+pub use digest_subscriptions::*;
+
+mod escalation_policies;
+mod escalation_policies_impl;
+pub use escalation_policies::*;
+
 mod incidents;
+mod incidents_export;
+mod incidents_flapping;
 mod incidents_impl;
+mod incidents_stats;
 pub use incidents::*;
 
+mod incidents_retention;
+mod incidents_retention_impl;
+pub use incidents_retention::*;
+
+mod notification_channels;
+mod notification_channels_impl;
+pub use notification_channels::*;
+
+mod notification_deliveries;
+mod notification_deliveries_impl;
+pub use notification_deliveries::*;
+
+mod oncall_schedules;
+mod oncall_schedules_impl;
+pub use oncall_schedules::*;
+
 pub mod qtype;
 
 static INTERVAL_RGX: Lazy<Regex> = Lazy::new(|| {
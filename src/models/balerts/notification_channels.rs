@@ -0,0 +1,140 @@
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::{Pg, PgValue},
+    serialize::{self, Output, ToSql},
+    sql_types::Jsonb,
+    *,
+};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::models::schema::notification_channels;
+
+/// A destination (owned by a customer) to which incidents events are sent
+#[derive(Identifiable, Queryable, Debug, Serialize, Deserialize, Clone, TS)]
+#[diesel(table_name = notification_channels)]
+#[ts(export)]
+pub struct NotificationChannels {
+    #[ts(type = "number")]
+    pub id: i64,
+    // The "owner" (organization) of the channel
+    pub cid: Uuid,
+    #[diesel(column_name = _name)]
+    pub name: String,
+    pub config: ChannelConfig,
+    pub active: bool,
+    // Only incidents with a severity >= min_severity are sent
+    pub min_severity: i32,
+    // Events sent to the channel (see IncidentEvent), empty means all of them
+    pub events: Vec<String>,
+    // Only send the incidents of this host (if defined)
+    pub host_uuid: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+// ================
+// Insertable model
+// ================
+#[derive(Insertable, Deserialize, Serialize, Debug, TS)]
+#[diesel(table_name = notification_channels)]
+#[ts(export)]
+pub struct NotificationChannelsDTO {
+    pub cid: Uuid,
+    #[diesel(column_name = _name)]
+    pub name: String,
+    pub config: ChannelConfig,
+    pub active: Option<bool>,
+    pub min_severity: Option<i32>,
+    pub events: Option<Vec<String>>,
+    pub host_uuid: Option<String>,
+}
+
+/// Using a specific struct for the Update allow us to pass all as None expect the fields we want to update
+#[derive(AsChangeset, Deserialize, Serialize, Debug, Default, TS)]
+#[diesel(table_name = notification_channels)]
+#[ts(export)]
+pub struct NotificationChannelsDTOUpdate {
+    #[diesel(column_name = _name)]
+    pub name: Option<String>,
+    pub config: Option<ChannelConfig>,
+    pub active: Option<bool>,
+    pub min_severity: Option<i32>,
+    pub events: Option<Vec<String>>,
+    pub host_uuid: Option<String>,
+}
+
+/// Configuration of a channel, depending on the way incidents are delivered
+///
+/// Stored as JSON in the config column, with its kind in the "type" field.
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, TS)]
+#[diesel(sql_type = Jsonb)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[ts(export)]
+pub enum ChannelConfig {
+    Email {
+        // Default to the emails of the members of the owner if empty
+        #[serde(default)]
+        recipients: Vec<String>,
+        // Also send to the user on call of this schedule (if defined)
+        #[serde(default)]
+        #[ts(type = "number | null")]
+        oncall: Option<i64>,
+    },
+    Webhook {
+        url: String,
+        // Key of the HMAC-SHA256 signature of the requests
+        secret: String,
+    },
+    // Incoming webhooks of the chat apps, the message is in their native format
+    Slack {
+        url: String,
+    },
+    Discord {
+        url: String,
+    },
+    Teams {
+        url: String,
+    },
+    Mattermost {
+        url: String,
+    },
+    // Receiver of the Prometheus Alertmanager webhooks
+    Alertmanager {
+        url: String,
+    },
+    // PagerDuty Events API v2
+    Pagerduty {
+        routing_key: String,
+        // Default to the PagerDuty events endpoint
+        #[serde(default)]
+        url: Option<String>,
+    },
+}
+
+/// Kind of a ChannelConfig, used to pick the Notifier in charge of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelKind {
+    Email,
+    Webhook,
+    Slack,
+    Discord,
+    Teams,
+    Mattermost,
+    Alertmanager,
+    Pagerduty,
+}
+
+impl FromSql<Jsonb, Pg> for ChannelConfig {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = <serde_json::Value as FromSql<Jsonb, Pg>>::from_sql(bytes)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl ToSql<Jsonb, Pg> for ChannelConfig {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        let value = serde_json::to_value(self)?;
+        <serde_json::Value as ToSql<Jsonb, Pg>>::to_sql(&value, &mut out.reborrow())
+    }
+}
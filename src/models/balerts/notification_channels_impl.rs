@@ -0,0 +1,249 @@
+use diesel::dsl::exists;
+use diesel::*;
+use uuid::Uuid;
+
+use super::{
+    ChannelConfig, ChannelKind, Incidents, NotificationChannels, NotificationChannelsDTO,
+    NotificationChannelsDTOUpdate,
+};
+use crate::apierrors::ApiError;
+use crate::models::schema::notification_channels::dsl::{
+    _name, active, cid, host_uuid, id, min_severity, notification_channels as dsl_channels,
+};
+use crate::models::{is_valid_email, BaseCrud, DtoBase};
+use crate::notifications::IncidentEvent;
+use crate::ConnType;
+
+impl ChannelConfig {
+    pub fn kind(&self) -> ChannelKind {
+        match self {
+            ChannelConfig::Email { .. } => ChannelKind::Email,
+            ChannelConfig::Webhook { .. } => ChannelKind::Webhook,
+            ChannelConfig::Slack { .. } => ChannelKind::Slack,
+            ChannelConfig::Discord { .. } => ChannelKind::Discord,
+            ChannelConfig::Teams { .. } => ChannelKind::Teams,
+            ChannelConfig::Mattermost { .. } => ChannelKind::Mattermost,
+            ChannelConfig::Alertmanager { .. } => ChannelKind::Alertmanager,
+            ChannelConfig::Pagerduty { .. } => ChannelKind::Pagerduty,
+        }
+    }
+
+    /// Assert that the configuration is usable to deliver the notifications
+    pub fn validate(&self) -> Result<(), ApiError> {
+        match self {
+            ChannelConfig::Email { recipients, .. } => {
+                if let Some(bad) = recipients.iter().find(|r| !is_valid_email(r)) {
+                    return Err(ApiError::InvalidRequestError(Some(format!(
+                        "channel: recipient {} is not a valid email address",
+                        bad
+                    ))));
+                }
+            }
+            ChannelConfig::Webhook { url, secret } => {
+                validate_url(url)?;
+                if secret.len() < 16 {
+                    return Err(ApiError::InvalidRequestError(Some(String::from(
+                        "channel: secret must be at least 16 characters long",
+                    ))));
+                }
+            }
+            ChannelConfig::Slack { url }
+            | ChannelConfig::Discord { url }
+            | ChannelConfig::Teams { url }
+            | ChannelConfig::Mattermost { url }
+            | ChannelConfig::Alertmanager { url } => validate_url(url)?,
+            ChannelConfig::Pagerduty { routing_key, url } => {
+                if routing_key.len() != 32 {
+                    return Err(ApiError::InvalidRequestError(Some(String::from(
+                        "channel: routing_key must be 32 characters long",
+                    ))));
+                }
+                if let Some(url) = url {
+                    validate_url(url)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_url(url: &str) -> Result<(), ApiError> {
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        return Err(ApiError::InvalidRequestError(Some(format!(
+            "channel: url {} must start with http:// or https://",
+            url
+        ))));
+    }
+
+    Ok(())
+}
+
+fn validate_events(events: &Option<Vec<String>>) -> Result<(), ApiError> {
+    if let Some(events) = events {
+        for event in events {
+            event.parse::<IncidentEvent>()?;
+        }
+    }
+
+    Ok(())
+}
+
+impl NotificationChannels {
+    /// Is the channel owned by one of the organizations of the user
+    /// - conn: the Database connection
+    /// - orgs: the UUIDs of the user's organizations (see Organizations::get_ids_by_member)
+    /// - chid: the id of the channel you want to check
+    pub fn exists_by_owner_and_id(
+        conn: &mut ConnType,
+        orgs: &[Uuid],
+        chid: i64,
+    ) -> Result<bool, ApiError> {
+        Ok(select(exists(
+            dsl_channels.filter(cid.eq_any(orgs).and(id.eq(chid))),
+        ))
+        .get_result(conn)?)
+    }
+
+    /// Get the active channels an event of the incident should be sent to
+    /// - conn: the Database connection
+    /// - incident: the incident the event is about
+    /// - event: the event to send
+    pub fn get_for_incident(
+        conn: &mut ConnType,
+        incident: &Incidents,
+        event: IncidentEvent,
+    ) -> Result<Vec<Self>, ApiError> {
+        let channels: Vec<Self> = dsl_channels
+            .filter(
+                cid.eq(incident.cid)
+                    .and(active.eq(true))
+                    .and(min_severity.le(incident.severity))
+                    .and(host_uuid.is_null().or(host_uuid.eq(&incident.host_uuid))),
+            )
+            .order_by(id.asc())
+            .load(conn)?;
+
+        Ok(channels
+            .into_iter()
+            .filter(|c| c.events.is_empty() || c.events.iter().any(|e| e == event.as_str()))
+            .collect())
+    }
+
+    /// Get the active channels of the organization among the ids
+    /// - conn: the Database connection
+    /// - ccid: the organization's UUID
+    /// - ids: the ids of the channels
+    pub fn get_active_by_ids(
+        conn: &mut ConnType,
+        ccid: &Uuid,
+        ids: &[i64],
+    ) -> Result<Vec<Self>, ApiError> {
+        Ok(dsl_channels
+            .filter(cid.eq(ccid).and(active.eq(true)).and(id.eq_any(ids)))
+            .order_by(id.asc())
+            .load(conn)?)
+    }
+}
+
+impl<'a> BaseCrud<'a> for NotificationChannels {
+    type RetType = NotificationChannels;
+
+    type VecRetType = Vec<Self::RetType>;
+
+    type TargetType = i64;
+
+    type UuidType = &'a Uuid;
+
+    /// Get all the channels defined by a user
+    /// - conn: the Database connection
+    /// - uuid: the targeted organization's UUID
+    /// - size: how many elements to return
+    /// - page: pagination :shrug:
+    fn get(
+        conn: &mut ConnType,
+        uuid: Self::UuidType,
+        size: i64,
+        page: i64,
+    ) -> Result<Self::VecRetType, ApiError> {
+        Ok(dsl_channels
+            .filter(cid.eq(uuid))
+            .limit(size)
+            .offset(page * size)
+            .order_by(_name.asc())
+            .load(conn)?)
+    }
+
+    /// Get a specific channel depending on the target_id
+    /// - conn: the Database connection
+    /// - target_id: the targeted channel's id
+    fn get_specific(
+        conn: &mut ConnType,
+        target_id: Self::TargetType,
+    ) -> Result<Self::RetType, ApiError> {
+        Ok(dsl_channels.find(target_id).first(conn)?)
+    }
+}
+
+impl<'a> DtoBase<'a> for NotificationChannels {
+    type GetReturn = NotificationChannels;
+
+    type InsertType = &'a NotificationChannelsDTO;
+
+    type UpdateType = &'a NotificationChannelsDTOUpdate;
+
+    type TargetType = i64;
+
+    type UpdateReturnType = Self::GetReturn;
+
+    fn insert(conn: &mut ConnType, value: Self::InsertType) -> Result<usize, ApiError> {
+        value.config.validate()?;
+        validate_events(&value.events)?;
+
+        Ok(insert_into(dsl_channels).values(value).execute(conn)?)
+    }
+
+    fn insert_and_get(
+        conn: &mut ConnType,
+        value: Self::InsertType,
+    ) -> Result<Self::GetReturn, ApiError> {
+        value.config.validate()?;
+        validate_events(&value.events)?;
+
+        Ok(insert_into(dsl_channels).values(value).get_result(conn)?)
+    }
+
+    fn update(
+        conn: &mut ConnType,
+        target_id: Self::TargetType,
+        value: Self::UpdateType,
+    ) -> Result<usize, ApiError> {
+        if let Some(config) = &value.config {
+            config.validate()?;
+        }
+        validate_events(&value.events)?;
+
+        Ok(update(dsl_channels.filter(id.eq(target_id)))
+            .set(value)
+            .execute(conn)?)
+    }
+
+    fn update_and_get(
+        conn: &mut ConnType,
+        target_id: Self::TargetType,
+        value: Self::UpdateType,
+    ) -> Result<Self::UpdateReturnType, ApiError> {
+        if let Some(config) = &value.config {
+            config.validate()?;
+        }
+        validate_events(&value.events)?;
+
+        Ok(update(dsl_channels.filter(id.eq(target_id)))
+            .set(value)
+            .get_result(conn)?)
+    }
+
+    fn delete(conn: &mut ConnType, target_id: Self::TargetType) -> Result<usize, ApiError> {
+        Ok(delete(dsl_channels.find(target_id)).execute(conn)?)
+    }
+}
@@ -0,0 +1,69 @@
+use diesel::*;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::models::schema::notification_deliveries;
+
+/// Log of the delivery of an incident event to a channel
+///
+/// The pending deliveries also act as the retry queue of the notifications.
+#[derive(Identifiable, Queryable, QueryableByName, Debug, Serialize, Deserialize, Clone, TS)]
+#[diesel(table_name = notification_deliveries)]
+#[ts(export)]
+pub struct NotificationDeliveries {
+    #[ts(type = "number")]
+    pub id: i64,
+    #[ts(type = "number")]
+    pub channel_id: i64,
+    pub incident_id: i32,
+    // The "owner" (organization) of the channel and incident
+    pub cid: Uuid,
+    // Event delivered (see IncidentEvent)
+    pub event: String,
+    // See DeliveryStatus
+    pub status: i32,
+    pub attempts: i32,
+    // HTTP status code of the last failed attempt (if any)
+    pub response_code: Option<i32>,
+    pub last_error: Option<String>,
+    // When the delivery will be (re)tried, None once delivered or failed
+    pub next_retry_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+// ================
+// Insertable model
+// ================
+#[derive(Insertable, Debug)]
+#[diesel(table_name = notification_deliveries)]
+pub struct NotificationDeliveriesDTO {
+    pub channel_id: i64,
+    pub incident_id: i32,
+    pub cid: Uuid,
+    pub event: String,
+    pub next_retry_at: Option<chrono::NaiveDateTime>,
+}
+
+/// Using a specific struct for the Update allow us to pass all as None expect the fields we want to update
+#[derive(AsChangeset, Debug, Default)]
+#[diesel(table_name = notification_deliveries)]
+pub struct NotificationDeliveriesDTOUpdate {
+    pub status: Option<i32>,
+    pub response_code: Option<Option<i32>>,
+    pub last_error: Option<Option<String>>,
+    pub next_retry_at: Option<Option<chrono::NaiveDateTime>>,
+    pub updated_at: Option<chrono::NaiveDateTime>,
+}
+
+/// Status of a delivery as stored in the status column
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+#[ts(export)]
+pub enum DeliveryStatus {
+    // Waiting for its first attempt or a retry
+    Pending = 0,
+    Delivered = 1,
+    // Every attempts failed, no more retry
+    Failed = 2,
+}
@@ -0,0 +1,156 @@
+use diesel::sql_types::{BigInt, Integer, Timestamp};
+use diesel::*;
+
+use super::{
+    DeliveryStatus, Incidents, NotificationChannels, NotificationDeliveries,
+    NotificationDeliveriesDTO, NotificationDeliveriesDTOUpdate,
+};
+use crate::apierrors::ApiError;
+use crate::models::schema::notification_deliveries::dsl::{
+    created_at, id, incident_id, notification_deliveries as dsl_deliveries, status,
+};
+use crate::notifications::{IncidentEvent, RetryPolicy};
+use crate::ConnType;
+
+impl NotificationDeliveries {
+    /// Add the deliveries of the event to the channels in the queue
+    /// - conn: the Database connection
+    /// - channels: the channels the event should be sent to
+    /// - incident: the incident the event is about
+    /// - event: the event to send
+    pub fn enqueue(
+        conn: &mut ConnType,
+        channels: &[NotificationChannels],
+        incident: &Incidents,
+        event: IncidentEvent,
+    ) -> Result<Vec<Self>, ApiError> {
+        let now = chrono::Utc::now().naive_utc();
+        let values: Vec<NotificationDeliveriesDTO> = channels
+            .iter()
+            .map(|channel| NotificationDeliveriesDTO {
+                channel_id: channel.id,
+                incident_id: incident.id,
+                cid: incident.cid,
+                event: event.to_string(),
+                next_retry_at: Some(now),
+            })
+            .collect();
+
+        Ok(insert_into(dsl_deliveries)
+            .values(&values)
+            .get_results(conn)?)
+    }
+
+    /// Take the pending deliveries which are due, for the worker to send them
+    /// - conn: the Database connection
+    /// - size: how many deliveries to take at most
+    /// - lease: for how long the deliveries are reserved to this worker
+    ///
+    /// The rows are locked using FOR UPDATE SKIP LOCKED so concurrent workers
+    /// never take the same deliveries. Instead of holding the lock while
+    /// sending, the next_retry_at is pushed by lease: a worker dying in the
+    /// middle only delays its deliveries. The attempts are counted here.
+    pub fn claim(
+        conn: &mut ConnType,
+        size: i64,
+        lease: chrono::Duration,
+    ) -> Result<Vec<Self>, ApiError> {
+        let now = chrono::Utc::now().naive_utc();
+
+        Ok(sql_query(
+            "
+			UPDATE notification_deliveries
+			SET attempts = attempts + 1, next_retry_at = $1, updated_at = $2
+			WHERE id IN (
+				SELECT id FROM notification_deliveries
+				WHERE status = $3 AND next_retry_at <= $2
+				ORDER BY next_retry_at
+				LIMIT $4
+				FOR UPDATE SKIP LOCKED
+			)
+			RETURNING *",
+        )
+        .bind::<Timestamp, _>(now + lease)
+        .bind::<Timestamp, _>(now)
+        .bind::<Integer, _>(DeliveryStatus::Pending as i32)
+        .bind::<BigInt, _>(size)
+        .load(conn)?)
+    }
+
+    /// Mark the delivery as successfully sent
+    /// - conn: the Database connection
+    pub fn mark_delivered(&self, conn: &mut ConnType) -> Result<usize, ApiError> {
+        let value = NotificationDeliveriesDTOUpdate {
+            status: Some(DeliveryStatus::Delivered as i32),
+            response_code: Some(None),
+            last_error: Some(None),
+            next_retry_at: Some(None),
+            updated_at: Some(chrono::Utc::now().naive_utc()),
+        };
+
+        Ok(update(dsl_deliveries.find(self.id))
+            .set(&value)
+            .execute(conn)?)
+    }
+
+    /// Record the failure of the attempt, scheduling a retry if any left
+    /// and if the error is retryable (see RetryPolicy::is_retryable)
+    /// - conn: the Database connection
+    /// - err: the error of the attempt
+    /// - retry: the policy deciding if and when the delivery is retried
+    pub fn mark_failed(
+        &self,
+        conn: &mut ConnType,
+        err: &ApiError,
+        retry: &RetryPolicy,
+    ) -> Result<usize, ApiError> {
+        let now = chrono::Utc::now().naive_utc();
+        let attempts = self.attempts.max(0) as u32;
+
+        let (new_status, next_retry) =
+            if attempts >= retry.max_attempts || !RetryPolicy::is_retryable(err) {
+                (DeliveryStatus::Failed, None)
+            } else {
+                let delay = chrono::Duration::from_std(retry.backoff(attempts))
+                    .unwrap_or_else(|_| chrono::Duration::zero());
+                (DeliveryStatus::Pending, Some(now + delay))
+            };
+
+        let value = NotificationDeliveriesDTOUpdate {
+            status: Some(new_status as i32),
+            response_code: Some(match err {
+                ApiError::HttpStatusError(code, _) => Some(*code as i32),
+                _ => None,
+            }),
+            last_error: Some(Some(err.to_string())),
+            next_retry_at: Some(next_retry),
+            updated_at: Some(now),
+        };
+
+        Ok(update(dsl_deliveries.find(self.id))
+            .set(&value)
+            .execute(conn)?)
+    }
+
+    /// Get the deliveries of the events of an incident
+    /// - conn: the Database connection
+    /// - target_id: the incident's id
+    /// - only: only return the deliveries with this status (if defined)
+    pub fn get_by_incident(
+        conn: &mut ConnType,
+        target_id: i32,
+        only: Option<DeliveryStatus>,
+    ) -> Result<Vec<Self>, ApiError> {
+        let mut query = dsl_deliveries
+            .filter(incident_id.eq(target_id))
+            .into_boxed();
+        if let Some(only) = only {
+            query = query.filter(status.eq(only as i32));
+        }
+
+        Ok(query
+            .order_by(created_at.desc())
+            .then_order_by(id.desc())
+            .load(conn)?)
+    }
+}
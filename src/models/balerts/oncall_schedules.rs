@@ -0,0 +1,103 @@
+use diesel::*;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::models::schema::{oncall_overrides, oncall_schedules};
+
+/// Rotation of a list of users, one of them being on call at a time
+///
+/// The shifts start at handoff_time (in the timezone of the schedule),
+/// the first one on start_date with the first member of the list.
+#[derive(Identifiable, Queryable, Debug, Serialize, Deserialize, Clone, TS)]
+#[diesel(table_name = oncall_schedules)]
+#[ts(export)]
+pub struct OncallSchedules {
+    #[ts(type = "number")]
+    pub id: i64,
+    // The "owner" (organization) of the schedule
+    pub cid: Uuid,
+    #[diesel(column_name = _name)]
+    pub name: String,
+    // IANA name of the timezone (eg: Europe/Paris)
+    pub timezone: String,
+    // See OncallRotation
+    pub rotation: i32,
+    pub handoff_time: chrono::NaiveTime,
+    pub start_date: chrono::NaiveDate,
+    // Users (customers) taking the shifts, in order
+    pub members: Vec<Uuid>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+// ================
+// Insertable model
+// ================
+#[derive(Insertable, Deserialize, Serialize, Debug, TS)]
+#[diesel(table_name = oncall_schedules)]
+#[ts(export)]
+pub struct OncallSchedulesDTO {
+    pub cid: Uuid,
+    #[diesel(column_name = _name)]
+    pub name: String,
+    pub timezone: Option<String>,
+    pub rotation: Option<i32>,
+    pub handoff_time: Option<chrono::NaiveTime>,
+    pub start_date: chrono::NaiveDate,
+    pub members: Vec<Uuid>,
+}
+
+/// Using a specific struct for the Update allow us to pass all as None expect the fields we want to update
+#[derive(AsChangeset, Deserialize, Serialize, Debug, Default, TS)]
+#[diesel(table_name = oncall_schedules)]
+#[ts(export)]
+pub struct OncallSchedulesDTOUpdate {
+    #[diesel(column_name = _name)]
+    pub name: Option<String>,
+    pub timezone: Option<String>,
+    pub rotation: Option<i32>,
+    pub handoff_time: Option<chrono::NaiveTime>,
+    pub start_date: Option<chrono::NaiveDate>,
+    pub members: Option<Vec<Uuid>>,
+}
+
+/// Length of the shifts as stored in the rotation column
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+#[ts(export)]
+pub enum OncallRotation {
+    Daily = 0,
+    Weekly = 1,
+}
+
+/// Temporary replacement of the on-call user of a schedule
+///
+/// Overrides win over the rotation, the latest created if they overlap.
+#[derive(Identifiable, Queryable, Debug, Serialize, Deserialize, Clone, TS)]
+#[diesel(table_name = oncall_overrides)]
+#[ts(export)]
+pub struct OncallOverrides {
+    #[ts(type = "number")]
+    pub id: i64,
+    #[ts(type = "number")]
+    pub schedule_id: i64,
+    // The user on call instead of the one of the rotation
+    pub user_id: Uuid,
+    // Bounds (UTC) of the override, ends_at being excluded
+    pub starts_at: chrono::NaiveDateTime,
+    pub ends_at: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+// ================
+// Insertable model
+// ================
+#[derive(Insertable, Deserialize, Serialize, Debug, TS)]
+#[diesel(table_name = oncall_overrides)]
+#[ts(export)]
+pub struct OncallOverridesDTO {
+    #[ts(type = "number")]
+    pub schedule_id: i64,
+    pub user_id: Uuid,
+    pub starts_at: chrono::NaiveDateTime,
+    pub ends_at: chrono::NaiveDateTime,
+}
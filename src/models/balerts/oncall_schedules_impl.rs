@@ -0,0 +1,474 @@
+use chrono::TimeZone;
+use chrono_tz::Tz;
+use diesel::dsl::exists;
+use diesel::*;
+use uuid::Uuid;
+
+use super::{
+    OncallOverrides, OncallOverridesDTO, OncallRotation, OncallSchedules, OncallSchedulesDTO,
+    OncallSchedulesDTOUpdate,
+};
+use crate::apierrors::ApiError;
+use crate::models::schema::oncall_overrides::dsl::{
+    created_at as ocreated_at, ends_at, id as oid, oncall_overrides as dsl_overrides, schedule_id,
+    starts_at,
+};
+use crate::models::schema::oncall_schedules::dsl::{
+    _name, cid, id, oncall_schedules as dsl_schedules,
+};
+use crate::models::{BaseCrud, Customers, DtoBase, Organizations};
+use crate::ConnType;
+
+impl OncallRotation {
+    /// Get the rotation from the value stored in the database
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(OncallRotation::Daily),
+            1 => Some(OncallRotation::Weekly),
+            _ => None,
+        }
+    }
+
+    /// Number of days of a shift
+    pub fn days(&self) -> i64 {
+        match self {
+            OncallRotation::Daily => 1,
+            OncallRotation::Weekly => 7,
+        }
+    }
+}
+
+/// Parse the IANA name of a timezone
+fn parse_timezone(value: &str) -> Result<Tz, ApiError> {
+    value.parse::<Tz>().map_err(|_| {
+        ApiError::InvalidRequestError(Some(format!("oncall: timezone {} is invalid", value)))
+    })
+}
+
+fn parse_rotation(value: i32) -> Result<OncallRotation, ApiError> {
+    OncallRotation::from_i32(value).ok_or_else(|| {
+        ApiError::InvalidRequestError(Some(format!(
+            "oncall: rotation {} is invalid. Valid are: 0 (daily), 1 (weekly).",
+            value
+        )))
+    })
+}
+
+/// Assert that the user is a member of the organization owning the schedule
+fn validate_member(conn: &mut ConnType, org: &Uuid, user: &Uuid) -> Result<(), ApiError> {
+    if !Organizations::is_member(conn, org, user)? {
+        return Err(ApiError::InvalidRequestError(Some(format!(
+            "oncall: {} is not a member of the organization",
+            user
+        ))));
+    }
+
+    Ok(())
+}
+
+fn validate_members(conn: &mut ConnType, org: &Uuid, members: &[Uuid]) -> Result<(), ApiError> {
+    if members.is_empty() {
+        return Err(ApiError::InvalidRequestError(Some(String::from(
+            "oncall: at least one member is needed",
+        ))));
+    }
+
+    for member in members {
+        validate_member(conn, org, member)?;
+    }
+
+    Ok(())
+}
+
+/// Get the organization owning the schedule
+fn schedule_owner(conn: &mut ConnType, sid: i64) -> Result<Uuid, ApiError> {
+    Ok(dsl_schedules.find(sid).select(cid).first(conn)?)
+}
+
+impl OncallSchedules {
+    /// Is the schedule owned by one of the organizations of the user
+    /// - conn: the Database connection
+    /// - orgs: the UUIDs of the user's organizations (see Organizations::get_ids_by_member)
+    /// - sid: the id of the schedule you want to check
+    pub fn exists_by_owner_and_id(
+        conn: &mut ConnType,
+        orgs: &[Uuid],
+        sid: i64,
+    ) -> Result<bool, ApiError> {
+        Ok(select(exists(
+            dsl_schedules.filter(cid.eq_any(orgs).and(id.eq(sid))),
+        ))
+        .get_result(conn)?)
+    }
+
+    /// Get the user on call according to the rotation only (no overrides)
+    /// - at: the time (UTC) to check
+    ///
+    /// Return None before the first shift of the schedule. The handoffs happen
+    /// at the local handoff_time, so the shifts follow the DST changes.
+    pub fn rotation_at(&self, at: chrono::NaiveDateTime) -> Result<Option<Uuid>, ApiError> {
+        if self.members.is_empty() {
+            return Ok(None);
+        }
+
+        let rotation = parse_rotation(self.rotation)?;
+        let local = parse_timezone(&self.timezone)?
+            .from_utc_datetime(&at)
+            .naive_local();
+
+        // Before the handoff, the shift of the previous day is still running
+        let mut day = local.date();
+        if local.time() < self.handoff_time {
+            day -= chrono::Duration::days(1);
+        }
+
+        let days = (day - self.start_date).num_days();
+        if days < 0 {
+            return Ok(None);
+        }
+
+        let shift = (days / rotation.days()) as usize;
+        Ok(Some(self.members[shift % self.members.len()]))
+    }
+
+    /// Get the user on call at the specific time
+    /// - conn: the Database connection
+    /// - at: the time (UTC) to check
+    ///
+    /// An override covering the time wins over the rotation.
+    pub fn oncall_at(
+        &self,
+        conn: &mut ConnType,
+        at: chrono::NaiveDateTime,
+    ) -> Result<Option<Uuid>, ApiError> {
+        match OncallOverrides::get_active(conn, self.id, at)? {
+            Some(ovr) => Ok(Some(ovr.user_id)),
+            None => self.rotation_at(at),
+        }
+    }
+
+    /// Get the user on call for a schedule of the owner
+    /// - conn: the Database connection
+    /// - ccid: the owner's UUID
+    /// - sid: the id of the schedule
+    /// - at: the time (UTC) to check
+    pub fn resolve(
+        conn: &mut ConnType,
+        ccid: &Uuid,
+        sid: i64,
+        at: chrono::NaiveDateTime,
+    ) -> Result<Option<Customers>, ApiError> {
+        let schedule: Self = dsl_schedules
+            .filter(cid.eq(ccid).and(id.eq(sid)))
+            .first(conn)?;
+
+        match schedule.oncall_at(conn, at)? {
+            Some(user) => Ok(Some(Customers::get_by_id(conn, &user)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl OncallOverrides {
+    /// Get the override of the schedule covering the specific time (if any)
+    /// - conn: the Database connection
+    /// - sid: the id of the schedule
+    /// - at: the time (UTC) to check
+    pub fn get_active(
+        conn: &mut ConnType,
+        sid: i64,
+        at: chrono::NaiveDateTime,
+    ) -> Result<Option<Self>, ApiError> {
+        Ok(dsl_overrides
+            .filter(
+                schedule_id
+                    .eq(sid)
+                    .and(starts_at.le(at))
+                    .and(ends_at.gt(at)),
+            )
+            .order_by(ocreated_at.desc())
+            .first(conn)
+            .optional()?)
+    }
+
+    /// Get the overrides of the schedule which are not over yet
+    /// - conn: the Database connection
+    /// - sid: the id of the schedule
+    /// - now: the current time
+    pub fn get_upcoming(
+        conn: &mut ConnType,
+        sid: i64,
+        now: chrono::NaiveDateTime,
+    ) -> Result<Vec<Self>, ApiError> {
+        Ok(dsl_overrides
+            .filter(schedule_id.eq(sid).and(ends_at.gt(now)))
+            .order_by(starts_at.asc())
+            .load(conn)?)
+    }
+
+    /// Add an override to a schedule
+    /// - conn: the Database connection
+    /// - value: the override to add
+    ///
+    /// The user must be a member of the organization owning the schedule.
+    pub fn insert(conn: &mut ConnType, value: &OncallOverridesDTO) -> Result<Self, ApiError> {
+        if value.ends_at <= value.starts_at {
+            return Err(ApiError::InvalidRequestError(Some(String::from(
+                "oncall: ends_at must be after starts_at",
+            ))));
+        }
+        let org = schedule_owner(conn, value.schedule_id)?;
+        validate_member(conn, &org, &value.user_id)?;
+
+        Ok(insert_into(dsl_overrides).values(value).get_result(conn)?)
+    }
+
+    /// Delete an override
+    /// - conn: the Database connection
+    /// - target_id: the id of the override
+    pub fn delete(conn: &mut ConnType, target_id: i64) -> Result<usize, ApiError> {
+        Ok(delete(dsl_overrides.filter(oid.eq(target_id))).execute(conn)?)
+    }
+}
+
+impl<'a> BaseCrud<'a> for OncallSchedules {
+    type RetType = OncallSchedules;
+
+    type VecRetType = Vec<Self::RetType>;
+
+    type TargetType = i64;
+
+    type UuidType = &'a Uuid;
+
+    /// Get all the on-call schedules defined by a user
+    /// - conn: the Database connection
+    /// - uuid: the targeted organization's UUID
+    /// - size: how many elements to return
+    /// - page: pagination :shrug:
+    fn get(
+        conn: &mut ConnType,
+        uuid: Self::UuidType,
+        size: i64,
+        page: i64,
+    ) -> Result<Self::VecRetType, ApiError> {
+        Ok(dsl_schedules
+            .filter(cid.eq(uuid))
+            .limit(size)
+            .offset(page * size)
+            .order_by(_name.asc())
+            .load(conn)?)
+    }
+
+    /// Get a specific schedule depending on the target_id
+    /// - conn: the Database connection
+    /// - target_id: the targeted schedule's id
+    fn get_specific(
+        conn: &mut ConnType,
+        target_id: Self::TargetType,
+    ) -> Result<Self::RetType, ApiError> {
+        Ok(dsl_schedules.find(target_id).first(conn)?)
+    }
+}
+
+impl OncallSchedulesDTO {
+    /// Assert that the schedule is usable
+    /// - conn: the Database connection
+    ///
+    /// The members must be members of the organization owning the schedule.
+    pub fn validate(&self, conn: &mut ConnType) -> Result<(), ApiError> {
+        if let Some(timezone) = &self.timezone {
+            parse_timezone(timezone)?;
+        }
+        if let Some(rotation) = self.rotation {
+            parse_rotation(rotation)?;
+        }
+        validate_members(conn, &self.cid, &self.members)
+    }
+}
+
+impl OncallSchedulesDTOUpdate {
+    /// Assert that the updated fields are usable
+    /// - conn: the Database connection
+    /// - org: the UUID of the organization owning the schedule
+    pub fn validate(&self, conn: &mut ConnType, org: &Uuid) -> Result<(), ApiError> {
+        if let Some(timezone) = &self.timezone {
+            parse_timezone(timezone)?;
+        }
+        if let Some(rotation) = self.rotation {
+            parse_rotation(rotation)?;
+        }
+        if let Some(members) = &self.members {
+            validate_members(conn, org, members)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> DtoBase<'a> for OncallSchedules {
+    type GetReturn = OncallSchedules;
+
+    type InsertType = &'a OncallSchedulesDTO;
+
+    type UpdateType = &'a OncallSchedulesDTOUpdate;
+
+    type TargetType = i64;
+
+    type UpdateReturnType = Self::GetReturn;
+
+    fn insert(conn: &mut ConnType, value: Self::InsertType) -> Result<usize, ApiError> {
+        value.validate(conn)?;
+
+        Ok(insert_into(dsl_schedules).values(value).execute(conn)?)
+    }
+
+    fn insert_and_get(
+        conn: &mut ConnType,
+        value: Self::InsertType,
+    ) -> Result<Self::GetReturn, ApiError> {
+        value.validate(conn)?;
+
+        Ok(insert_into(dsl_schedules).values(value).get_result(conn)?)
+    }
+
+    fn update(
+        conn: &mut ConnType,
+        target_id: Self::TargetType,
+        value: Self::UpdateType,
+    ) -> Result<usize, ApiError> {
+        let org = schedule_owner(conn, target_id)?;
+        value.validate(conn, &org)?;
+
+        Ok(update(dsl_schedules.filter(id.eq(target_id)))
+            .set(value)
+            .execute(conn)?)
+    }
+
+    fn update_and_get(
+        conn: &mut ConnType,
+        target_id: Self::TargetType,
+        value: Self::UpdateType,
+    ) -> Result<Self::UpdateReturnType, ApiError> {
+        let org = schedule_owner(conn, target_id)?;
+        value.validate(conn, &org)?;
+
+        Ok(update(dsl_schedules.filter(id.eq(target_id)))
+            .set(value)
+            .get_result(conn)?)
+    }
+
+    fn delete(conn: &mut ConnType, target_id: Self::TargetType) -> Result<usize, ApiError> {
+        Ok(delete(dsl_schedules.find(target_id)).execute(conn)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: Uuid = Uuid::from_u128(1);
+    const BOB: Uuid = Uuid::from_u128(2);
+    const CAROL: Uuid = Uuid::from_u128(3);
+
+    /// Schedule in Paris (UTC+1, UTC+2 from 2026-03-29), handoff at 09:00, starting on Monday 2026-03-23
+    fn schedule(rotation: OncallRotation, members: Vec<Uuid>) -> OncallSchedules {
+        OncallSchedules {
+            id: 1,
+            cid: Uuid::nil(),
+            name: String::from("primary"),
+            timezone: String::from("Europe/Paris"),
+            rotation: rotation as i32,
+            handoff_time: chrono::NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            start_date: chrono::NaiveDate::from_ymd_opt(2026, 3, 23).unwrap(),
+            members,
+            created_at: chrono::NaiveDateTime::default(),
+        }
+    }
+
+    fn utc(value: &str) -> chrono::NaiveDateTime {
+        chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn nobody_before_the_start() {
+        let schedule = schedule(OncallRotation::Daily, vec![ALICE, BOB, CAROL]);
+
+        assert_eq!(schedule.rotation_at(utc("2026-03-22 12:00")).unwrap(), None);
+        // 08:00 in Paris, the first shift starts at the handoff
+        assert_eq!(schedule.rotation_at(utc("2026-03-23 07:00")).unwrap(), None);
+        assert_eq!(
+            schedule.rotation_at(utc("2026-03-23 08:00")).unwrap(),
+            Some(ALICE)
+        );
+    }
+
+    #[test]
+    fn daily_handoff() {
+        let schedule = schedule(OncallRotation::Daily, vec![ALICE, BOB, CAROL]);
+
+        // 08:30 and 09:30 in Paris
+        assert_eq!(
+            schedule.rotation_at(utc("2026-03-24 07:30")).unwrap(),
+            Some(ALICE)
+        );
+        assert_eq!(
+            schedule.rotation_at(utc("2026-03-24 08:30")).unwrap(),
+            Some(BOB)
+        );
+        assert_eq!(
+            schedule.rotation_at(utc("2026-03-26 08:30")).unwrap(),
+            Some(ALICE)
+        );
+    }
+
+    #[test]
+    fn weekly_rollover() {
+        let schedule = schedule(OncallRotation::Weekly, vec![ALICE, BOB]);
+
+        // Monday 08:30 and 09:30 in Paris (UTC+2)
+        assert_eq!(
+            schedule.rotation_at(utc("2026-03-30 06:30")).unwrap(),
+            Some(ALICE)
+        );
+        assert_eq!(
+            schedule.rotation_at(utc("2026-03-30 07:30")).unwrap(),
+            Some(BOB)
+        );
+        // Back to the first member after everyone took a week
+        assert_eq!(
+            schedule.rotation_at(utc("2026-04-06 07:30")).unwrap(),
+            Some(ALICE)
+        );
+    }
+
+    #[test]
+    fn handoff_follows_the_dst() {
+        let schedule = schedule(OncallRotation::Daily, vec![ALICE, BOB, CAROL]);
+
+        // 08:00 UTC is the handoff before the DST change, 07:00 UTC after
+        assert_eq!(
+            schedule.rotation_at(utc("2026-03-28 07:30")).unwrap(),
+            Some(BOB)
+        );
+        assert_eq!(
+            schedule.rotation_at(utc("2026-03-28 08:30")).unwrap(),
+            Some(CAROL)
+        );
+        assert_eq!(
+            schedule.rotation_at(utc("2026-03-29 06:30")).unwrap(),
+            Some(CAROL)
+        );
+        assert_eq!(
+            schedule.rotation_at(utc("2026-03-29 07:30")).unwrap(),
+            Some(ALICE)
+        );
+    }
+
+    #[test]
+    fn invalid_timezone() {
+        let mut schedule = schedule(OncallRotation::Daily, vec![ALICE]);
+        schedule.timezone = String::from("Mars/Olympus");
+
+        assert!(schedule.rotation_at(utc("2026-03-24 12:00")).is_err());
+    }
+}
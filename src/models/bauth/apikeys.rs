@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
 use diesel::*;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
@@ -5,24 +8,105 @@ use uuid::Uuid;
 
 use crate::models::schema::apikeys;
 
+/// Api Key used by the agents to authenticate
+///
+/// The key itself is never stored, only its public prefix
+/// (to look it up) and the SHA-256 hash of its secret.
 #[derive(Debug, Queryable, QueryableByName, Serialize, Deserialize, TS)]
 #[diesel(table_name = apikeys)]
 #[ts(export)]
 pub struct ApiKey {
     #[ts(type = "number")]
     pub id: i64,
-    pub key: String,
     pub host_uuid: Option<String>,
+    // The organization owning the key (and its host)
     pub customer_id: Uuid,
     pub berta: String,
+    pub prefix: String,
+    #[serde(skip)]
+    pub key_hash: String,
+    // What the key is allowed to do (see ApiKeyScope)
+    pub scopes: Vec<String>,
+    pub created_at: chrono::NaiveDateTime,
+    // The key is refused from this time (if defined)
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    // Updated in batches, so it can lag behind a bit
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub revoked_at: Option<chrono::NaiveDateTime>,
+    // Created by an enrollment token, a host has at most one of these keys
+    pub enrolled: bool,
 }
 
-/// Using a specific struct for the Update allow us to pass all as None expect the fields we want to update
-#[derive(Insertable, AsChangeset, Deserialize, Serialize, Debug, Default)]
+/// Insertable struct (no id fields => which is auto generated)
+///
+/// The prefix and key_hash come from a GeneratedApiKey (see with_key),
+/// and only the enrollment marks a key as enrolled.
+#[derive(Insertable, Deserialize, Serialize, Debug, Default)]
 #[diesel(table_name = apikeys)]
 pub struct ApiKeyDTO {
-    pub key: Option<String>,
     pub host_uuid: Option<String>,
     pub customer_id: Option<Uuid>,
     pub berta: Option<String>,
+    #[serde(skip)]
+    pub prefix: Option<String>,
+    #[serde(skip)]
+    pub key_hash: Option<String>,
+    pub scopes: Option<Vec<String>>,
+    pub expires_at: Option<Option<chrono::NaiveDateTime>>,
+    #[serde(skip)]
+    pub enrolled: Option<bool>,
+}
+
+/// Using a specific struct for the Update allow us to pass all as None expect the fields we want to update
+///
+/// Only the fields editable by the user, the key itself is changed by a
+/// rotation and a revocation is final (see ApiKey::rotate and ApiKey::revoke).
+#[derive(AsChangeset, Deserialize, Serialize, Debug, Default)]
+#[diesel(table_name = apikeys)]
+pub struct ApiKeyDTOUpdate {
+    pub host_uuid: Option<String>,
+    pub scopes: Option<Vec<String>>,
+    pub expires_at: Option<Option<chrono::NaiveDateTime>>,
+}
+
+/// Permission granted to an Api Key, as stored in the scopes column
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum ApiKeyScope {
+    // Push the metrics of its host
+    Ingest,
+    // Read the metrics of the hosts (eg: dashboards)
+    ReadMetrics,
+    // Create, update and delete the alerts
+    ManageAlerts,
+    // Read and acknowledge the incidents
+    ReadIncidents,
+    // Everything above
+    Admin,
+}
+
+/// Newly generated Api Key, the only time the plaintext key is known
+///
+/// The key must be given to the user right away, as it cannot be recovered.
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct GeneratedApiKey {
+    // Full key (sp_<prefix>_<secret>) to hand out to the user
+    pub key: String,
+    pub prefix: String,
+    #[serde(skip)]
+    pub key_hash: String,
+    // Key of the HMAC of the signed requests (see RequestVerifier), to hand out with the key
+    pub signing_key: String,
+}
+
+/// Last use of the Api Keys, waiting to be written in the database
+///
+/// Recording a use is only a memory write, so it can be done on the
+/// hot ingest path. A background task calls flush periodically to
+/// update the last_used_at of all the keys in a single query.
+#[derive(Debug, Default)]
+pub struct ApiKeyUsage {
+    pub(crate) pending: Mutex<HashMap<i64, chrono::NaiveDateTime>>,
 }
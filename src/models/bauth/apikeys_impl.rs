@@ -1,17 +1,262 @@
+use std::fmt;
+use std::str::FromStr;
+
 use diesel::dsl::exists;
+use diesel::sql_types::{Array, Int8, Timestamp};
 use diesel::*;
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
-use super::{ApiKey, ApiKeyDTO};
+use super::organizations_impl::org_ids_of;
+use super::{
+    ApiKey, ApiKeyDTO, ApiKeyDTOUpdate, ApiKeyScope, ApiKeyUsage, GeneratedApiKey, Organizations,
+    RequestVerifier,
+};
 use crate::apierrors::ApiError;
 use crate::models::schema::apikeys::dsl::{
-    apikeys as dsl_apikeys, berta, customer_id, host_uuid, id, key,
+    apikeys as dsl_apikeys, berta, customer_id, expires_at, host_uuid, id, prefix, revoked_at,
 };
 use crate::models::{BaseCrud, DtoBase, ExtCrud};
 use crate::ConnType;
 
+/// Marker of the keys generated in the prefix + secret form
+const KEY_MARKER: &str = "sp_";
+
+/// Length of the public prefix of the keys
+///
+/// The keys created before the hashing are looked up using the first
+/// PREFIX_LEN characters of their hash (see the migration).
+pub(super) const PREFIX_LEN: usize = 8;
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::Ingest => "ingest",
+            ApiKeyScope::ReadMetrics => "read_metrics",
+            ApiKeyScope::ManageAlerts => "manage_alerts",
+            ApiKeyScope::ReadIncidents => "read_incidents",
+            ApiKeyScope::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for ApiKeyScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ApiKeyScope {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ingest" => Ok(ApiKeyScope::Ingest),
+            "read_metrics" => Ok(ApiKeyScope::ReadMetrics),
+            "manage_alerts" => Ok(ApiKeyScope::ManageAlerts),
+            "read_incidents" => Ok(ApiKeyScope::ReadIncidents),
+            "admin" => Ok(ApiKeyScope::Admin),
+            _ => Err(ApiError::InvalidRequestError(Some(format!(
+                "scope: {} is invalid. Valid are: ingest, read_metrics, manage_alerts, read_incidents, admin.",
+                s
+            )))),
+        }
+    }
+}
+
+/// Assert that the scopes (if defined) are known and not empty
+fn validate_scopes(scopes: &Option<Vec<String>>) -> Result<(), ApiError> {
+    if let Some(scopes) = scopes {
+        if scopes.is_empty() {
+            return Err(ApiError::InvalidRequestError(Some(String::from(
+                "scope: at least one scope is needed",
+            ))));
+        }
+        for scope in scopes {
+            scope.parse::<ApiKeyScope>()?;
+        }
+    }
+
+    Ok(())
+}
+
+impl GeneratedApiKey {
+    /// Generate a new random key
+    /// - verifier: the verifier of the signed requests, deriving the signing key
+    pub fn new(verifier: &RequestVerifier) -> Self {
+        let (public, secret) = random_prefix_and_secret();
+        let key = format!("{}{}_{}", KEY_MARKER, public, secret);
+
+        Self {
+            signing_key: verifier.signing_key(&key),
+            key,
+            key_hash: hash_secret(&secret),
+            prefix: public,
+        }
+    }
+}
+
+/// Generate a random public prefix and secret
+///
+/// The randomness comes from UUIDs v4 (using the OS RNG),
+/// giving a secret of 244 random bits.
+pub(super) fn random_prefix_and_secret() -> (String, String) {
+    let public = Uuid::new_v4().simple().to_string()[..PREFIX_LEN].to_owned();
+    let secret = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+
+    (public, secret)
+}
+
+impl ApiKeyDTO {
+    /// Set the prefix and hash of the generated key, to insert or rotate it
+    pub fn with_key(self, generated: &GeneratedApiKey) -> Self {
+        Self {
+            prefix: Some(generated.prefix.clone()),
+            key_hash: Some(generated.key_hash.clone()),
+            ..self
+        }
+    }
+}
+
+/// Split a key into its public prefix and its secret
+///
+/// Keys not in the sp_<prefix>_<secret> form are the old plaintext
+/// ones, for which the whole key is the secret and the prefix is
+/// taken from its hash, so no part of the key is stored.
+pub(super) fn split_key(hkey: &str) -> (String, &str) {
+    if let Some((public, secret)) = hkey
+        .strip_prefix(KEY_MARKER)
+        .and_then(|rest| rest.split_once('_'))
+    {
+        if public.len() == PREFIX_LEN && !secret.is_empty() {
+            return (public.to_owned(), secret);
+        }
+    }
+
+    (hash_secret(hkey)[..PREFIX_LEN].to_owned(), hkey)
+}
+
+/// Hash the secret of a key (or token) as stored in the database
+pub(super) fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+/// Compare the two values in constant time (relative to their content)
+pub(super) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Get the key (among the ones sharing its prefix) matching the secret
+///
+/// Fail with a NotFound, as the direct lookup by key used to.
+fn find_matching(candidates: Vec<ApiKey>, secret: &str) -> Result<ApiKey, ApiError> {
+    let hashed = hash_secret(secret);
+
+    candidates
+        .into_iter()
+        .find(|candidate| constant_time_eq(candidate.key_hash.as_bytes(), hashed.as_bytes()))
+        .ok_or(ApiError::DieselError(diesel::result::Error::NotFound))
+}
+
 impl ApiKey {
-    /// Get the Api Key object owned by user with secret value
+    /// Is the key allowed to perform the operations of the scope
+    ///
+    /// The admin scope grants all of them. Unknown scopes
+    /// (eg: stored by a newer version) are ignored.
+    pub fn has_scope(&self, scope: ApiKeyScope) -> bool {
+        self.scopes
+            .iter()
+            .filter_map(|s| s.parse::<ApiKeyScope>().ok())
+            .any(|s| s == scope || s == ApiKeyScope::Admin)
+    }
+
+    /// Assert that the key is allowed to perform the operations of the scope
+    ///
+    /// Meant to be called by the services before each operation done using a key.
+    pub fn authorize(&self, scope: ApiKeyScope) -> Result<(), ApiError> {
+        if self.has_scope(scope) {
+            return Ok(());
+        }
+
+        Err(ApiError::AuthorizationError(Some(format!(
+            "the api key is missing the {} scope",
+            scope
+        ))))
+    }
+
+    /// Assert that the key is not revoked nor expired
+    pub fn usable(self) -> Result<Self, ApiError> {
+        let now = chrono::Utc::now().naive_utc();
+
+        if self.revoked_at.is_some_and(|at| at <= now) {
+            return Err(ApiError::ApiKeyRefusedError(Some(String::from(
+                "the api key has been revoked",
+            ))));
+        }
+        if self.expires_at.is_some_and(|at| at <= now) {
+            return Err(ApiError::ApiKeyRefusedError(Some(String::from(
+                "the api key has expired",
+            ))));
+        }
+
+        Ok(self)
+    }
+
+    /// Replace the key by a new one with the same host, berta and scopes
+    /// - conn: the Database connection
+    /// - kid: the id of the key to rotate
+    /// - overlap: for how long the old key keeps working
+    /// - verifier: the verifier of the signed requests, deriving the signing key
+    ///
+    /// Both keys work during the overlap, so the agents can be updated
+    /// without downtime. The new key is returned along with its plaintext.
+    pub fn rotate(
+        conn: &mut ConnType,
+        kid: i64,
+        overlap: chrono::Duration,
+        verifier: &RequestVerifier,
+    ) -> Result<(Self, GeneratedApiKey), ApiError> {
+        conn.transaction(|conn| {
+            let old = Self::get_specific(conn, kid)?.usable()?;
+            let generated = GeneratedApiKey::new(verifier);
+
+            let value = ApiKeyDTO {
+                host_uuid: old.host_uuid.clone(),
+                customer_id: Some(old.customer_id),
+                berta: Some(old.berta.clone()),
+                scopes: Some(old.scopes.clone()),
+                expires_at: Some(old.expires_at),
+                ..Default::default()
+            }
+            .with_key(&generated);
+            let new: Self = insert_into(dsl_apikeys).values(&value).get_result(conn)?;
+
+            // Never extend the life of the old key
+            let end = chrono::Utc::now().naive_utc() + overlap;
+            update(dsl_apikeys.find(old.id))
+                .set(expires_at.eq(Some(old.expires_at.map_or(end, |at| at.min(end)))))
+                .execute(conn)?;
+
+            Ok((new, generated))
+        })
+    }
+
+    /// Revoke the key, which is refused from now on
+    /// - conn: the Database connection
+    /// - kid: the id of the key to revoke
+    pub fn revoke(conn: &mut ConnType, kid: i64) -> Result<usize, ApiError> {
+        Ok(
+            update(dsl_apikeys.filter(id.eq(kid).and(revoked_at.is_null())))
+                .set(revoked_at.eq(chrono::Utc::now().naive_utc()))
+                .execute(conn)?,
+        )
+    }
+
+    /// Get the Api Key object owned by (an organization of the) user with secret value
     /// - conn: the Database connection
     /// - cid: the user's UUID
     /// - hkey: the api key you want to get info of
@@ -20,18 +265,25 @@ impl ApiKey {
         cid: &Uuid,
         hkey: &str,
     ) -> Result<Self, ApiError> {
-        Ok(dsl_apikeys
-            .filter(customer_id.eq(cid).and(key.eq(hkey)))
-            .first(conn)?)
+        let (public, secret) = split_key(hkey);
+        let candidates = dsl_apikeys
+            .filter(customer_id.eq_any(org_ids_of(cid)).and(prefix.eq(public)))
+            .load(conn)?;
+
+        find_matching(candidates, secret)?.usable()
     }
 
+    /// Get the Api Key object owned by (an organization of the) user by its id
+    /// - conn: the Database connection
+    /// - cid: the user's UUID
+    /// - kid: the id of the key
     pub fn get_by_keyid_and_owner(
         conn: &mut ConnType,
         cid: &Uuid,
         kid: i64,
     ) -> Result<Self, ApiError> {
         Ok(dsl_apikeys
-            .filter(customer_id.eq(cid).and(id.eq(kid)))
+            .filter(customer_id.eq_any(org_ids_of(cid)).and(id.eq(kid)))
             .first(conn)?)
     }
 
@@ -39,7 +291,15 @@ impl ApiKey {
     /// - conn: the Database connection
     /// - hkey: the api key you want to get info of
     pub fn get_by_key(conn: &mut ConnType, hkey: &str) -> Result<Self, ApiError> {
-        Ok(dsl_apikeys.filter(key.eq(hkey)).first(conn)?)
+        Self::find_by_key(conn, hkey)?.usable()
+    }
+
+    /// Same as get_by_key but also returning the expired and revoked keys
+    fn find_by_key(conn: &mut ConnType, hkey: &str) -> Result<Self, ApiError> {
+        let (public, secret) = split_key(hkey);
+        let candidates = dsl_apikeys.filter(prefix.eq(public)).load(conn)?;
+
+        find_matching(candidates, secret)
     }
 
     /// Get the Api Key object by the secret value and the berta host
@@ -51,12 +311,15 @@ impl ApiKey {
         hkey: &str,
         cberta: &str,
     ) -> Result<Self, ApiError> {
-        Ok(dsl_apikeys
-            .filter(key.eq(hkey).and(berta.eq(cberta)))
-            .first(conn)?)
+        let (public, secret) = split_key(hkey);
+        let candidates = dsl_apikeys
+            .filter(prefix.eq(public).and(berta.eq(cberta)))
+            .load(conn)?;
+
+        find_matching(candidates, secret)?.usable()
     }
 
-    /// Does the Api Key object owned by user for the specified host exists?
+    /// Does the Api Key object owned by (an organization of the) user for the specified host exists?
     /// - conn: the Database connection
     /// - cid: the user's UUID
     /// - huuid: the targeted host's uuid
@@ -65,13 +328,13 @@ impl ApiKey {
         cid: &Uuid,
         huuid: &str,
     ) -> Result<bool, ApiError> {
-        Ok(select(exists(
-            dsl_apikeys.filter(customer_id.eq(cid).and(host_uuid.eq(huuid))),
-        ))
+        Ok(select(exists(dsl_apikeys.filter(
+            customer_id.eq_any(org_ids_of(cid)).and(host_uuid.eq(huuid)),
+        )))
         .get_result(conn)?)
     }
 
-    /// Does the Api Key object owned by user with the specified secret?
+    /// Does the Api Key object owned by (an organization of the) user with the specified secret?
     /// - conn: the Database connection
     /// - cid: the user's UUID
     /// - hkey: the api key you want to get info of
@@ -80,13 +343,14 @@ impl ApiKey {
         cid: &Uuid,
         hkey: &str,
     ) -> Result<bool, ApiError> {
-        Ok(select(exists(
-            dsl_apikeys.filter(customer_id.eq(cid).and(key.eq(hkey))),
-        ))
-        .get_result(conn)?)
+        match Self::find_by_key(conn, hkey) {
+            Ok(apikey) => Organizations::is_member(conn, &apikey.customer_id, cid),
+            Err(ApiError::DieselError(diesel::result::Error::NotFound)) => Ok(false),
+            Err(err) => Err(err),
+        }
     }
 
-    /// Get all the Api Keys object owned by user
+    /// Get the hosts of all the organizations of the user
     /// - conn: the Database connection
     /// - cid: the user's UUID
     /// - size: how many elements to return
@@ -99,7 +363,11 @@ impl ApiKey {
     ) -> Result<Vec<String>, ApiError> {
         let res = dsl_apikeys
             .select(host_uuid)
-            .filter(customer_id.eq(cid).and(host_uuid.is_not_null()))
+            .filter(
+                customer_id
+                    .eq_any(org_ids_of(cid))
+                    .and(host_uuid.is_not_null()),
+            )
             .limit(size)
             .offset(page * size)
             .order_by(host_uuid.asc())
@@ -109,6 +377,47 @@ impl ApiKey {
     }
 }
 
+impl ApiKeyUsage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the key has just been used
+    pub fn touch(&self, kid: i64) {
+        let now = chrono::Utc::now().naive_utc();
+        // The lock only guard the map, a poisoned one is still usable
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.insert(kid, now);
+    }
+
+    /// Write the recorded uses in the database, in a single query
+    /// - conn: the Database connection
+    ///
+    /// Return the number of keys updated. On error, the uses
+    /// are lost, which is fine as the next ones will be recorded.
+    pub fn flush(&self, conn: &mut ConnType) -> Result<usize, ApiError> {
+        let pending = {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            std::mem::take(&mut *pending)
+        };
+        if pending.is_empty() {
+            return Ok(0);
+        }
+
+        let (ids, times): (Vec<i64>, Vec<chrono::NaiveDateTime>) = pending.into_iter().unzip();
+        Ok(sql_query(
+            "
+			UPDATE apikeys SET last_used_at = u.used_at
+			FROM unnest($1, $2) AS u(id, used_at)
+			WHERE apikeys.id = u.id
+				AND (apikeys.last_used_at IS NULL OR apikeys.last_used_at < u.used_at)",
+        )
+        .bind::<Array<Int8>, _>(ids)
+        .bind::<Array<Timestamp>, _>(times)
+        .execute(conn)?)
+    }
+}
+
 impl<'a> BaseCrud<'a> for ApiKey {
     type RetType = ApiKey;
 
@@ -118,9 +427,9 @@ impl<'a> BaseCrud<'a> for ApiKey {
 
     type UuidType = &'a Uuid;
 
-    /// Get all the Api Key defined for a user
+    /// Get all the Api Key defined for an organization
     /// - conn: the Database connection
-    /// - uuid: the targeted's user UUID
+    /// - uuid: the targeted organization's UUID
     /// - size: how many elements to return
     /// - page: pagination :shrug:
     fn get(
@@ -169,13 +478,15 @@ impl<'a> DtoBase<'a> for ApiKey {
 
     type InsertType = &'a ApiKeyDTO;
 
-    type UpdateType = Self::InsertType;
+    type UpdateType = &'a ApiKeyDTOUpdate;
 
     type TargetType = &'a str;
 
     type UpdateReturnType = Self::GetReturn;
 
     fn insert(conn: &mut ConnType, value: Self::InsertType) -> Result<usize, ApiError> {
+        validate_scopes(&value.scopes)?;
+
         Ok(insert_into(dsl_apikeys).values(value).execute(conn)?)
     }
 
@@ -183,6 +494,8 @@ impl<'a> DtoBase<'a> for ApiKey {
         conn: &mut ConnType,
         value: Self::InsertType,
     ) -> Result<Self::GetReturn, ApiError> {
+        validate_scopes(&value.scopes)?;
+
         Ok(insert_into(dsl_apikeys).values(value).get_result(conn)?)
     }
 
@@ -191,7 +504,10 @@ impl<'a> DtoBase<'a> for ApiKey {
         target_id: Self::TargetType,
         value: Self::UpdateType,
     ) -> Result<usize, ApiError> {
-        Ok(update(dsl_apikeys.filter(key.eq(target_id)))
+        validate_scopes(&value.scopes)?;
+        let target = Self::find_by_key(conn, target_id)?;
+
+        Ok(update(dsl_apikeys.find(target.id))
             .set(value)
             .execute(conn)?)
     }
@@ -201,12 +517,17 @@ impl<'a> DtoBase<'a> for ApiKey {
         target_id: Self::TargetType,
         value: Self::UpdateType,
     ) -> Result<Self::UpdateReturnType, ApiError> {
-        Ok(update(dsl_apikeys.filter(key.eq(target_id)))
+        validate_scopes(&value.scopes)?;
+        let target = Self::find_by_key(conn, target_id)?;
+
+        Ok(update(dsl_apikeys.find(target.id))
             .set(value)
             .get_result(conn)?)
     }
 
     fn delete(conn: &mut ConnType, target_id: Self::TargetType) -> Result<usize, ApiError> {
-        Ok(delete(dsl_apikeys.filter(key.eq(target_id))).execute(conn)?)
+        let target = Self::find_by_key(conn, target_id)?;
+
+        Ok(delete(dsl_apikeys.find(target.id)).execute(conn)?)
     }
 }
@@ -0,0 +1,474 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use diesel::*;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use super::apikeys_impl::{hash_secret, split_key};
+use super::ApiKey;
+use crate::apierrors::ApiError;
+use crate::models::schema::apikeys::dsl::{apikeys as dsl_apikeys, berta, prefix};
+use crate::ConnType;
+
+/// Header holding the public prefix of the Api Key which signed the request
+pub const SIGNED_KEY_HEADER: &str = "X-Sproot-Key";
+/// Header holding the unix timestamp (in seconds) at which the request was signed
+pub const SIGNED_TIMESTAMP_HEADER: &str = "X-Sproot-Timestamp";
+/// Header holding a random value, unique to each request
+pub const SIGNED_NONCE_HEADER: &str = "X-Sproot-Nonce";
+/// Header holding the signature of the request (sha256=<hex>)
+pub const SIGNED_SIGNATURE_HEADER: &str = "X-Sproot-Signature";
+
+/// Request signed by an agent, as received by the server
+///
+/// Instead of sending its Api Key, the agent sends the prefix of the key
+/// and an HMAC-SHA256 of the request. The HMAC key is the signing key of
+/// the Api Key (see RequestVerifier::signing_key), which is never stored.
+#[derive(Debug)]
+pub struct SignedRequest<'a> {
+    // Value of the SIGNED_KEY_HEADER
+    pub key_prefix: &'a str,
+    pub method: &'a str,
+    // Path (and query) of the request
+    pub path: &'a str,
+    // Value of the SIGNED_TIMESTAMP_HEADER
+    pub timestamp: i64,
+    // Value of the SIGNED_NONCE_HEADER
+    pub nonce: &'a str,
+    pub body: &'a [u8],
+    // Value of the SIGNED_SIGNATURE_HEADER
+    pub signature: &'a str,
+}
+
+/// Compute the signature of a request (as an agent would)
+/// - hkey: the Api Key of the agent
+/// - signing_key: the signing key given to the agent with its Api Key
+/// - method: the HTTP method of the request
+/// - path: the path (and query) of the request
+/// - timestamp: the unix timestamp sent in the SIGNED_TIMESTAMP_HEADER
+/// - nonce: the value sent in the SIGNED_NONCE_HEADER
+/// - body: the body of the request
+///
+/// Return the prefix of the key (for the SIGNED_KEY_HEADER) and the signature.
+pub fn sign_request(
+    hkey: &str,
+    signing_key: &str,
+    method: &str,
+    path: &str,
+    timestamp: i64,
+    nonce: &str,
+    body: &[u8],
+) -> (String, String) {
+    let (public, _) = split_key(hkey);
+    let mac = request_mac(signing_key, method, path, timestamp, nonce, body);
+
+    (
+        public,
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes())),
+    )
+}
+
+/// The signed content is "{METHOD}\n{path}\n{timestamp}\n{nonce}\n{hex(sha256(body))}"
+fn request_mac(
+    signing_key: &str,
+    method: &str,
+    path: &str,
+    timestamp: i64,
+    nonce: &str,
+    body: &[u8],
+) -> Hmac<Sha256> {
+    // HMAC accept keys of any size, new_from_slice cannot fail
+    let mut mac = Hmac::<Sha256>::new_from_slice(signing_key.as_bytes()).unwrap();
+    mac.update(method.to_ascii_uppercase().as_bytes());
+    mac.update(b"\n");
+    mac.update(path.as_bytes());
+    mac.update(b"\n");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b"\n");
+    mac.update(nonce.as_bytes());
+    mac.update(b"\n");
+    mac.update(hex::encode(Sha256::digest(body)).as_bytes());
+    mac
+}
+
+/// Nonces recently seen, to refuse the replayed requests
+///
+/// A nonce only has to be remembered while its timestamp is accepted.
+#[derive(Debug, Default)]
+pub struct NonceCache {
+    // The lock only guard the maps, a poisoned one is still usable
+    inner: Mutex<NonceEntries>,
+}
+
+#[derive(Debug, Default)]
+struct NonceEntries {
+    // (key prefix, nonce) => timestamp after which the entry can be forgotten
+    seen: HashMap<(String, String), i64>,
+    // timestamp after which the entries can be forgotten => entries, in order
+    expiries: BTreeMap<i64, Vec<(String, String)>>,
+}
+
+impl NonceCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the nonce, return false if it was already seen
+    /// - key_prefix: the prefix of the key which signed the request
+    /// - nonce: the nonce of the request
+    /// - forget_at: unix timestamp after which the nonce can be forgotten
+    /// - now: the current unix timestamp
+    pub fn insert(&self, key_prefix: &str, nonce: &str, forget_at: i64, now: i64) -> bool {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let NonceEntries { seen, expiries } = &mut *inner;
+
+        // Only walk the expired entries, at the front of the queue
+        while let Some(entry) = expiries.first_entry() {
+            if *entry.key() >= now {
+                break;
+            }
+            for key in entry.remove() {
+                seen.remove(&key);
+            }
+        }
+
+        let key = (key_prefix.to_owned(), nonce.to_owned());
+        if seen.contains_key(&key) {
+            return false;
+        }
+        seen.insert(key.clone(), forget_at);
+        expiries.entry(forget_at).or_default().push(key);
+        true
+    }
+}
+
+/// Context of the derivation of the signing keys
+const SIGNING_KEY_CONTEXT: &[u8] = b"sproot-request-signing\n";
+
+/// Check the signed requests of the agents
+///
+/// One verifier (and so one NonceCache) is meant to be shared by all the
+/// workers of a server. With multiple servers, a replay can still be
+/// accepted once per server during the skew window.
+pub struct RequestVerifier {
+    // Maximum difference (in seconds) between the timestamp and the server clock
+    skew: i64,
+    // Secret of the server, from which the signing keys are derived
+    secret: Vec<u8>,
+    nonces: NonceCache,
+}
+
+impl std::fmt::Debug for RequestVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestVerifier")
+            .field("skew", &self.skew)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RequestVerifier {
+    /// Create a verifier accepting a clock difference of skew
+    /// - skew: the maximum difference between the timestamp and the server clock
+    /// - secret: the secret of the server, which must be the same on every server
+    pub fn new(skew: chrono::Duration, secret: &[u8]) -> Self {
+        Self {
+            skew: skew.num_seconds().abs(),
+            secret: secret.to_vec(),
+            nonces: NonceCache::new(),
+        }
+    }
+
+    /// Get the signing key of the Api Key, to hand out to the agent with its key
+    /// - hkey: the Api Key of the agent
+    pub fn signing_key(&self, hkey: &str) -> String {
+        let (_, secret) = split_key(hkey);
+        self.derive(&hash_secret(secret))
+    }
+
+    /// Derive the signing key from the stored hash of the key
+    ///
+    /// Keyed by the secret of the server, so reading the database
+    /// (and the key_hash) is not enough to sign requests.
+    fn derive(&self, key_hash: &str) -> String {
+        // HMAC accept keys of any size, new_from_slice cannot fail
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).unwrap();
+        mac.update(SIGNING_KEY_CONTEXT);
+        mac.update(key_hash.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// Get the Api Key which signed the request, if its signature is valid
+    /// - conn: the Database connection
+    /// - request: the signed request
+    /// - cberta: the berta on which the api key is allowed
+    ///
+    /// Same as ApiKey::get_by_key_berta for the signed requests. Every
+    /// failure is an AuthorizationError holding the reason.
+    pub fn verify(
+        &self,
+        conn: &mut ConnType,
+        request: &SignedRequest,
+        cberta: &str,
+    ) -> Result<ApiKey, ApiError> {
+        let candidates: Vec<ApiKey> = dsl_apikeys
+            .filter(prefix.eq(request.key_prefix).and(berta.eq(cberta)))
+            .load(conn)?;
+
+        self.check(request, candidates, chrono::Utc::now().timestamp())
+    }
+
+    /// Find the key (among the candidates) which signed the request
+    /// - request: the signed request
+    /// - candidates: the keys sharing the prefix of the request
+    /// - now: the current unix timestamp
+    fn check(
+        &self,
+        request: &SignedRequest,
+        candidates: Vec<ApiKey>,
+        now: i64,
+    ) -> Result<ApiKey, ApiError> {
+        let refuse = |reason: &str| ApiError::AuthorizationError(Some(reason.to_owned()));
+
+        if (now - request.timestamp).abs() > self.skew {
+            return Err(refuse(
+                "signature: the timestamp is outside of the allowed window",
+            ));
+        }
+        if request.nonce.is_empty() || request.nonce.len() > 64 {
+            return Err(refuse("signature: the nonce must be 1 to 64 characters"));
+        }
+        let signature = match request.signature.strip_prefix("sha256=").map(hex::decode) {
+            Some(Ok(bytes)) => bytes,
+            _ => return Err(refuse("signature: the signature is malformed")),
+        };
+
+        let apikey = candidates
+            .into_iter()
+            .find(|candidate| {
+                // verify_slice compare in constant time
+                request_mac(
+                    &self.derive(&candidate.key_hash),
+                    request.method,
+                    request.path,
+                    request.timestamp,
+                    request.nonce,
+                    request.body,
+                )
+                .verify_slice(&signature)
+                .is_ok()
+            })
+            .ok_or_else(|| refuse("signature: the signature doesn't match"))?
+            .usable()
+            .map_err(|err| match err {
+                ApiError::ApiKeyRefusedError(reason) => ApiError::AuthorizationError(reason),
+                err => err,
+            })?;
+
+        // Only record the nonce of valid requests, so they can't be used to fill the cache
+        if !self.nonces.insert(
+            request.key_prefix,
+            request.nonce,
+            request.timestamp + self.skew,
+            now,
+        ) {
+            return Err(refuse("signature: the request has already been received"));
+        }
+
+        Ok(apikey)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::models::{ApiKeyScope, GeneratedApiKey};
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn verifier() -> RequestVerifier {
+        RequestVerifier::new(chrono::Duration::minutes(5), b"server-secret")
+    }
+
+    fn apikey(generated: &GeneratedApiKey) -> ApiKey {
+        ApiKey {
+            id: 1,
+            host_uuid: None,
+            customer_id: Uuid::nil(),
+            berta: String::from("berta"),
+            prefix: generated.prefix.clone(),
+            key_hash: generated.key_hash.clone(),
+            scopes: vec![ApiKeyScope::Ingest.to_string()],
+            created_at: chrono::DateTime::from_timestamp(NOW, 0)
+                .unwrap()
+                .naive_utc(),
+            expires_at: None,
+            last_used_at: None,
+            revoked_at: None,
+            enrolled: false,
+        }
+    }
+
+    /// Sign the request and check it as the server would
+    fn check(
+        verifier: &RequestVerifier,
+        generated: &GeneratedApiKey,
+        signing_key: &str,
+        timestamp: i64,
+        nonce: &str,
+        sent: &[u8],
+        received: &[u8],
+    ) -> Result<ApiKey, ApiError> {
+        let (key_prefix, signature) = sign_request(
+            &generated.key,
+            signing_key,
+            "post",
+            "/api/metrics",
+            timestamp,
+            nonce,
+            sent,
+        );
+        let request = SignedRequest {
+            key_prefix: &key_prefix,
+            method: "POST",
+            path: "/api/metrics",
+            timestamp,
+            nonce,
+            body: received,
+            signature: &signature,
+        };
+
+        verifier.check(&request, vec![apikey(generated)], NOW)
+    }
+
+    #[test]
+    fn round_trip() {
+        let verifier = verifier();
+        let generated = GeneratedApiKey::new(&verifier);
+        let signing_key = &generated.signing_key;
+
+        let apikey = check(&verifier, &generated, signing_key, NOW, "n1", b"{}", b"{}").unwrap();
+        assert_eq!(apikey.prefix, generated.prefix);
+    }
+
+    #[test]
+    fn timestamp_outside_of_the_skew() {
+        let verifier = verifier();
+        let generated = GeneratedApiKey::new(&verifier);
+        let signing_key = &generated.signing_key;
+
+        assert!(check(
+            &verifier,
+            &generated,
+            signing_key,
+            NOW - 301,
+            "n1",
+            b"",
+            b""
+        )
+        .is_err());
+        assert!(check(
+            &verifier,
+            &generated,
+            signing_key,
+            NOW + 301,
+            "n2",
+            b"",
+            b""
+        )
+        .is_err());
+        assert!(check(
+            &verifier,
+            &generated,
+            signing_key,
+            NOW - 300,
+            "n3",
+            b"",
+            b""
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn replayed_nonce() {
+        let verifier = verifier();
+        let generated = GeneratedApiKey::new(&verifier);
+        let signing_key = &generated.signing_key;
+
+        assert!(check(&verifier, &generated, signing_key, NOW, "n1", b"", b"").is_ok());
+        assert!(check(&verifier, &generated, signing_key, NOW, "n1", b"", b"").is_err());
+    }
+
+    #[test]
+    fn tampered_body() {
+        let verifier = verifier();
+        let generated = GeneratedApiKey::new(&verifier);
+        let signing_key = &generated.signing_key;
+
+        let res = check(
+            &verifier,
+            &generated,
+            signing_key,
+            NOW,
+            "n1",
+            b"{}",
+            b"{\"a\":1}",
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn stored_hash_cannot_sign() {
+        let verifier = verifier();
+        let generated = GeneratedApiKey::new(&verifier);
+
+        let res = check(
+            &verifier,
+            &generated,
+            &generated.key_hash,
+            NOW,
+            "n1",
+            b"",
+            b"",
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn signing_key_depends_on_the_server_secret() {
+        let verifier = verifier();
+        let generated = GeneratedApiKey::new(&verifier);
+        let other = RequestVerifier::new(chrono::Duration::minutes(5), b"other-secret");
+
+        assert_eq!(generated.signing_key, verifier.signing_key(&generated.key));
+        assert_ne!(generated.signing_key, other.signing_key(&generated.key));
+        // Signed with the key of another server
+        let res = check(
+            &verifier,
+            &generated,
+            &other.signing_key(&generated.key),
+            NOW,
+            "n1",
+            b"",
+            b"",
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn nonce_forgotten_once_expired() {
+        let cache = NonceCache::new();
+
+        assert!(cache.insert("p1", "n1", NOW + 10, NOW));
+        assert!(cache.insert("p1", "n2", NOW + 20, NOW));
+        assert!(!cache.insert("p1", "n1", NOW + 10, NOW + 10));
+        assert!(cache.insert("p2", "n1", NOW + 10, NOW + 10));
+
+        // n1 of both keys are expired, n2 is still remembered
+        assert!(cache.insert("p1", "n1", NOW + 30, NOW + 11));
+        assert!(!cache.insert("p1", "n2", NOW + 30, NOW + 11));
+        assert_eq!(cache.inner.lock().unwrap().expiries.len(), 2);
+    }
+}
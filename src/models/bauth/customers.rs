@@ -1,4 +1,10 @@
-use diesel::*;
+use diesel::{
+    deserialize::{self, FromSql},
+    pg::{Pg, PgValue},
+    serialize::{self, Output, ToSql},
+    sql_types::Jsonb,
+    *,
+};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 use uuid::Uuid;
@@ -11,6 +17,28 @@ use crate::models::schema::customers;
 pub struct Customers {
     pub id: Uuid,
     pub email: String,
+    pub display_name: Option<String>,
+    // IANA name of the timezone (eg: Europe/Paris)
+    pub timezone: String,
+    pub notification_prefs: NotificationPreferences,
+    // New email waiting for its confirmation (if any)
+    pub pending_email: Option<String>,
+    #[serde(skip)]
+    pub email_token_hash: Option<String>,
+    #[serde(skip)]
+    pub email_token_expires_at: Option<chrono::NaiveDateTime>,
+    // Secret (hex) of the TOTP authenticator, pending until enabled
+    #[serde(skip)]
+    pub totp_secret: Option<String>,
+    // The second factor is required to log in from this time (if defined)
+    pub totp_enabled_at: Option<chrono::NaiveDateTime>,
+    // Last time step accepted, so a code can't be used twice
+    #[serde(skip)]
+    pub totp_last_step: Option<i64>,
+    #[serde(skip)]
+    pub totp_failures: i32,
+    #[serde(skip)]
+    pub totp_locked_until: Option<chrono::NaiveDateTime>,
 }
 
 // ================
@@ -21,3 +49,62 @@ pub struct Customers {
 pub struct CustomersDTO<'a> {
     pub email: &'a str,
 }
+
+/// Using a specific struct for the Update allow us to pass all as None expect the fields we want to update
+///
+/// The email is not part of it, as changing it must be confirmed
+/// (see Customers::request_email_change).
+#[derive(AsChangeset, Deserialize, Serialize, Debug, Default, TS)]
+#[diesel(table_name = customers)]
+#[ts(export)]
+pub struct CustomersDTOUpdate {
+    pub display_name: Option<Option<String>>,
+    pub timezone: Option<String>,
+    pub notification_prefs: Option<NotificationPreferences>,
+}
+
+/// What the user wants to be notified of, stored as JSON in the notification_prefs column
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Debug, Clone, PartialEq, TS)]
+#[diesel(sql_type = Jsonb)]
+#[serde(default)]
+#[ts(export)]
+pub struct NotificationPreferences {
+    // Receive the incidents sent by email to the owner of the channels
+    pub incident_emails: bool,
+    // Receive the digests sent to the owner of the subscriptions
+    pub digest_emails: bool,
+    // Receive the product announcements
+    pub newsletter: bool,
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        Self {
+            incident_emails: true,
+            digest_emails: true,
+            newsletter: false,
+        }
+    }
+}
+
+/// Kind of the emails the users can opt out of (see NotificationPreferences)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailKind {
+    Incident,
+    Digest,
+    Newsletter,
+}
+
+impl FromSql<Jsonb, Pg> for NotificationPreferences {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let value = <serde_json::Value as FromSql<Jsonb, Pg>>::from_sql(bytes)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl ToSql<Jsonb, Pg> for NotificationPreferences {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        let value = serde_json::to_value(self)?;
+        <serde_json::Value as ToSql<Jsonb, Pg>>::to_sql(&value, &mut out.reborrow())
+    }
+}
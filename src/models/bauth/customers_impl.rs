@@ -1,13 +1,56 @@
+use chrono_tz::Tz;
 use diesel::dsl::exists;
+use diesel::result::DatabaseErrorKind;
 use diesel::*;
 use uuid::Uuid;
 
-use super::{Customers, CustomersDTO};
+use super::apikeys_impl::{hash_secret, random_prefix_and_secret};
+use super::organizations_impl::{assert_not_last_owner, revoke_credentials};
+use super::{
+    Customers, CustomersDTO, CustomersDTOUpdate, EmailKind, NotificationPreferences, Organizations,
+};
 use crate::apierrors::ApiError;
-use crate::models::schema::customers::dsl::{customers as dsl_customers, email, id};
-use crate::models::DtoBase;
+use crate::models::schema::customers::dsl::{
+    customers as dsl_customers, email, email_token_expires_at, email_token_hash, id, pending_email,
+};
+use crate::models::schema::organizations;
+use crate::models::{is_valid_email, DtoBase};
 use crate::ConnType;
 
+impl NotificationPreferences {
+    /// Does the user want to receive this kind of emails
+    pub fn allows(&self, kind: EmailKind) -> bool {
+        match kind {
+            EmailKind::Incident => self.incident_emails,
+            EmailKind::Digest => self.digest_emails,
+            EmailKind::Newsletter => self.newsletter,
+        }
+    }
+}
+
+impl CustomersDTOUpdate {
+    /// Assert that the updated fields are usable
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if let Some(timezone) = &self.timezone {
+            if timezone.parse::<Tz>().is_err() {
+                return Err(ApiError::InvalidRequestError(Some(format!(
+                    "customer: timezone {} is invalid",
+                    timezone
+                ))));
+            }
+        }
+        if let Some(Some(name)) = &self.display_name {
+            if name.trim().is_empty() || name.len() > 128 {
+                return Err(ApiError::InvalidRequestError(Some(String::from(
+                    "customer: display_name must be 1 to 128 characters",
+                ))));
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl Customers {
     /// Get the user object by email address
     /// - conn: the Database connection
@@ -16,6 +59,105 @@ impl Customers {
         Ok(dsl_customers.filter(email.eq(mail)).first(conn)?)
     }
 
+    /// Get the user object by its UUID
+    /// - conn: the Database connection
+    /// - cid: the user's UUID
+    pub fn get_by_id(conn: &mut ConnType, cid: &Uuid) -> Result<Customers, ApiError> {
+        Ok(dsl_customers.find(cid).first(conn)?)
+    }
+
+    /// Start the change of the email of the user
+    /// - conn: the Database connection
+    /// - cid: the user's UUID
+    /// - new_email: the new email address
+    /// - ttl: for how long the change can be confirmed
+    ///
+    /// Return the token to send to the new address, which is needed to
+    /// confirm the change. Only the last requested change can be confirmed.
+    pub fn request_email_change(
+        conn: &mut ConnType,
+        cid: &Uuid,
+        new_email: &str,
+        ttl: chrono::Duration,
+    ) -> Result<String, ApiError> {
+        if !is_valid_email(new_email) {
+            return Err(ApiError::InvalidRequestError(Some(format!(
+                "customer: {} is not a valid email address",
+                new_email
+            ))));
+        }
+        if select(exists(dsl_customers.filter(email.eq(new_email)))).get_result::<bool>(conn)? {
+            return Err(ApiError::InvalidRequestError(Some(String::from(
+                "customer: the email address is already used",
+            ))));
+        }
+
+        let (_, token) = random_prefix_and_secret();
+        let updated = update(dsl_customers.find(cid))
+            .set((
+                pending_email.eq(new_email),
+                email_token_hash.eq(hash_secret(&token)),
+                email_token_expires_at.eq(chrono::Utc::now().naive_utc() + ttl),
+            ))
+            .execute(conn)?;
+        if updated != 1 {
+            return Err(ApiError::DieselError(diesel::result::Error::NotFound));
+        }
+
+        Ok(token)
+    }
+
+    /// Confirm the change of email, swapping the email for the pending one
+    /// - conn: the Database connection
+    /// - token: the token sent to the new address
+    ///
+    /// The swap is done in a single statement, so the token can only be used once.
+    pub fn confirm_email_change(conn: &mut ConnType, token: &str) -> Result<Customers, ApiError> {
+        let res = update(
+            dsl_customers.filter(
+                email_token_hash
+                    .eq(hash_secret(token))
+                    .and(email_token_expires_at.gt(chrono::Utc::now().naive_utc()))
+                    .and(pending_email.is_not_null()),
+            ),
+        )
+        .set((
+            email.eq(pending_email.assume_not_null()),
+            pending_email.eq(None::<String>),
+            email_token_hash.eq(None::<String>),
+            email_token_expires_at.eq(None::<chrono::NaiveDateTime>),
+        ))
+        .get_result(conn)
+        .optional();
+
+        match res {
+            Ok(Some(customer)) => Ok(customer),
+            Ok(None) => Err(ApiError::AuthorizationError(Some(String::from(
+                "the email confirmation token is invalid or expired",
+            )))),
+            // Someone else took the address since the change was requested
+            Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                Err(ApiError::InvalidRequestError(Some(String::from(
+                    "customer: the email address is already used",
+                ))))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Cancel the pending change of email (if any)
+    /// - conn: the Database connection
+    /// - cid: the user's UUID
+    pub fn cancel_email_change(conn: &mut ConnType, cid: &Uuid) -> Result<usize, ApiError> {
+        Ok(update(dsl_customers.find(cid))
+            .set((
+                pending_email.eq(None::<String>),
+                email_token_hash.eq(None::<String>),
+                email_token_expires_at.eq(None::<chrono::NaiveDateTime>),
+            ))
+            .execute(conn)?)
+    }
+
     /// Does the user exists?
     /// - conn: the Database connection
     /// - cid: the user's UUID
@@ -29,41 +171,70 @@ impl<'a> DtoBase<'a> for Customers {
 
     type InsertType = &'a CustomersDTO<'a>;
 
-    // useless :)
-    type UpdateType = i8;
+    type UpdateType = &'a CustomersDTOUpdate;
 
     type TargetType = &'a Uuid;
 
     type UpdateReturnType = Self::GetReturn;
 
     fn insert(conn: &mut ConnType, value: Self::InsertType) -> Result<usize, ApiError> {
-        Ok(insert_into(dsl_customers).values(value).execute(conn)?)
+        Self::insert_and_get(conn, value).map(|_| 1)
     }
 
+    /// Insert the customer along with its personal organization
     fn insert_and_get(
         conn: &mut ConnType,
         value: Self::InsertType,
     ) -> Result<Self::GetReturn, ApiError> {
-        Ok(insert_into(dsl_customers).values(value).get_result(conn)?)
+        conn.transaction(|conn| {
+            let customer: Customers = insert_into(dsl_customers).values(value).get_result(conn)?;
+            Organizations::create_personal(conn, &customer)?;
+
+            Ok(customer)
+        })
     }
 
     fn update(
-        _conn: &mut ConnType,
-        _target_id: Self::TargetType,
-        _value: Self::UpdateType,
+        conn: &mut ConnType,
+        target_id: Self::TargetType,
+        value: Self::UpdateType,
     ) -> Result<usize, ApiError> {
-        todo!()
+        value.validate()?;
+
+        Ok(update(dsl_customers.find(target_id))
+            .set(value)
+            .execute(conn)?)
     }
 
     fn update_and_get(
-        _conn: &mut ConnType,
-        _target_id: Self::TargetType,
-        _value: Self::UpdateType,
+        conn: &mut ConnType,
+        target_id: Self::TargetType,
+        value: Self::UpdateType,
     ) -> Result<Self::GetReturn, ApiError> {
-        todo!()
+        value.validate()?;
+
+        Ok(update(dsl_customers.find(target_id))
+            .set(value)
+            .get_result(conn)?)
     }
 
+    /// Delete the customer along with its personal organization
+    ///
+    /// Refused while the customer is the last owner of a shared organization.
     fn delete(conn: &mut ConnType, target_id: Self::TargetType) -> Result<usize, ApiError> {
-        Ok(delete(dsl_customers.find(target_id)).execute(conn)?)
+        conn.transaction(|conn| {
+            assert_not_last_owner(conn, target_id)?;
+            revoke_credentials(conn, target_id)?;
+            delete(
+                organizations::table.filter(
+                    organizations::id
+                        .eq(target_id)
+                        .and(organizations::personal.eq(true)),
+                ),
+            )
+            .execute(conn)?;
+
+            Ok(delete(dsl_customers.find(target_id)).execute(conn)?)
+        })
     }
 }
@@ -0,0 +1,72 @@
+use diesel::*;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::models::schema::enrollment_tokens;
+
+/// Token exchanged by a fresh agent for its own Api Key
+///
+/// Like the Api Keys, only the public prefix and the
+/// SHA-256 hash of the secret of the token are stored.
+#[derive(Identifiable, Queryable, Debug, Serialize, Deserialize, Clone, TS)]
+#[diesel(table_name = enrollment_tokens)]
+#[ts(export)]
+pub struct EnrollmentTokens {
+    #[ts(type = "number")]
+    pub id: i64,
+    // The "owner" (organization) of the token, and of the hosts it enrolls
+    pub cid: Uuid,
+    #[diesel(column_name = _name)]
+    pub name: String,
+    pub prefix: String,
+    #[serde(skip)]
+    pub token_hash: String,
+    // The berta the enrolled hosts send their metrics to
+    pub berta: String,
+    // How many hosts can be enrolled with the token
+    pub max_uses: i32,
+    pub uses: i32,
+    pub expires_at: chrono::NaiveDateTime,
+    pub revoked_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+// ================
+// Insertable model
+// ================
+#[derive(Insertable, Debug)]
+#[diesel(table_name = enrollment_tokens)]
+pub struct EnrollmentTokensDTO {
+    pub cid: Uuid,
+    #[diesel(column_name = _name)]
+    pub name: String,
+    pub prefix: String,
+    pub token_hash: String,
+    pub berta: String,
+    pub max_uses: i32,
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+/// Request to create an enrollment token
+#[derive(Deserialize, Serialize, Debug, TS)]
+#[ts(export)]
+pub struct EnrollmentTokensRequest {
+    pub cid: Uuid,
+    pub name: String,
+    pub berta: String,
+    // Default to a single use
+    pub max_uses: Option<i32>,
+    // For how long (in seconds) the token can be used
+    pub ttl: i64,
+}
+
+/// Newly created enrollment token, the only time the plaintext token is known
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct GeneratedEnrollmentToken {
+    #[serde(flatten)]
+    pub enrollment: EnrollmentTokens,
+    // Full token (spe_<prefix>_<secret>) to give to the agents
+    pub token: String,
+}
@@ -0,0 +1,275 @@
+use diesel::dsl::exists;
+use diesel::result::DatabaseErrorKind;
+use diesel::*;
+use uuid::Uuid;
+
+use super::apikeys_impl::{constant_time_eq, hash_secret, random_prefix_and_secret, PREFIX_LEN};
+use super::{
+    ApiKey, ApiKeyDTO, ApiKeyScope, EnrollmentTokens, EnrollmentTokensDTO, EnrollmentTokensRequest,
+    GeneratedApiKey, GeneratedEnrollmentToken, RequestVerifier,
+};
+use crate::apierrors::ApiError;
+use crate::models::schema::apikeys::{self, dsl::apikeys as dsl_apikeys};
+use crate::models::schema::enrollment_tokens::dsl::{
+    cid, created_at, enrollment_tokens as dsl_enrollments, expires_at, id, max_uses, prefix,
+    revoked_at, uses,
+};
+use crate::models::BaseCrud;
+use crate::ConnType;
+
+/// Marker of the enrollment tokens, to tell them apart from the Api Keys
+const TOKEN_MARKER: &str = "spe_";
+
+/// Maximum length of a host_uuid given by an agent
+const MAX_HOST_UUID_LEN: usize = 64;
+
+/// Assert that the host_uuid given by an agent is usable
+fn validate_host_uuid(huuid: &str) -> Result<(), ApiError> {
+    if huuid.is_empty()
+        || huuid.len() > MAX_HOST_UUID_LEN
+        || !huuid
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(ApiError::InvalidRequestError(Some(String::from(
+            "enrollment: host_uuid must be 1 to 64 alphanumeric characters (or - and _)",
+        ))));
+    }
+
+    Ok(())
+}
+
+impl EnrollmentTokens {
+    /// Create a new enrollment token
+    /// - conn: the Database connection
+    /// - request: the owner, berta, uses and lifetime of the token
+    ///
+    /// The plaintext token is only returned here.
+    pub fn create(
+        conn: &mut ConnType,
+        request: &EnrollmentTokensRequest,
+    ) -> Result<GeneratedEnrollmentToken, ApiError> {
+        let uses_allowed = request.max_uses.unwrap_or(1);
+        if uses_allowed < 1 {
+            return Err(ApiError::InvalidRequestError(Some(String::from(
+                "enrollment: max_uses must be at least 1",
+            ))));
+        }
+        if request.ttl <= 0 {
+            return Err(ApiError::InvalidRequestError(Some(String::from(
+                "enrollment: ttl must be positive",
+            ))));
+        }
+
+        let (public, secret) = random_prefix_and_secret();
+        let value = EnrollmentTokensDTO {
+            cid: request.cid,
+            name: request.name.clone(),
+            prefix: public.clone(),
+            token_hash: hash_secret(&secret),
+            berta: request.berta.clone(),
+            max_uses: uses_allowed,
+            expires_at: chrono::Utc::now().naive_utc() + chrono::Duration::seconds(request.ttl),
+        };
+
+        Ok(GeneratedEnrollmentToken {
+            enrollment: insert_into(dsl_enrollments)
+                .values(&value)
+                .get_result(conn)?,
+            token: format!("{}{}_{}", TOKEN_MARKER, public, secret),
+        })
+    }
+
+    /// Get the enrollment token by its plaintext value
+    /// - conn: the Database connection
+    /// - token: the token given by the agent
+    ///
+    /// Expired, revoked and exhausted tokens are returned as well.
+    pub fn get_by_token(conn: &mut ConnType, token: &str) -> Result<Self, ApiError> {
+        let not_found = || ApiError::DieselError(diesel::result::Error::NotFound);
+        let (public, secret) = token
+            .strip_prefix(TOKEN_MARKER)
+            .and_then(|rest| rest.split_once('_'))
+            .filter(|(public, _)| public.len() == PREFIX_LEN)
+            .ok_or_else(not_found)?;
+        let hashed = hash_secret(secret);
+
+        dsl_enrollments
+            .filter(prefix.eq(public))
+            .load::<Self>(conn)?
+            .into_iter()
+            .find(|candidate| constant_time_eq(candidate.token_hash.as_bytes(), hashed.as_bytes()))
+            .ok_or_else(not_found)
+    }
+
+    /// Assert that the token can still enroll hosts
+    pub fn usable(&self) -> Result<(), ApiError> {
+        let reason = if self.revoked_at.is_some() {
+            "the enrollment token has been revoked"
+        } else if self.expires_at <= chrono::Utc::now().naive_utc() {
+            "the enrollment token has expired"
+        } else if self.uses >= self.max_uses {
+            "the enrollment token has already been used"
+        } else {
+            return Ok(());
+        };
+
+        Err(ApiError::AuthorizationError(Some(String::from(reason))))
+    }
+
+    /// Exchange an enrollment token for the Api Key of a new host
+    /// - conn: the Database connection
+    /// - token: the token given by the agent
+    /// - huuid: the host_uuid of the agent, generated if None
+    /// - verifier: the verifier of the signed requests, deriving the signing key
+    ///
+    /// The key is owned by the owner of the token, bound to its berta and
+    /// only allowed to ingest the metrics. A host_uuid already having a usable
+    /// key in the organization is refused (enforced by a unique index for the
+    /// concurrent enrollments), so a token cannot be used to take over a host.
+    pub fn enroll(
+        conn: &mut ConnType,
+        token: &str,
+        huuid: Option<&str>,
+        verifier: &RequestVerifier,
+    ) -> Result<(ApiKey, GeneratedApiKey), ApiError> {
+        let huuid = match huuid {
+            Some(huuid) => {
+                validate_host_uuid(huuid)?;
+                huuid.to_owned()
+            }
+            None => Uuid::new_v4().to_string(),
+        };
+
+        conn.transaction(|conn| {
+            let enrollment = Self::get_by_token(conn, token)?;
+            enrollment.usable()?;
+
+            let now = chrono::Utc::now().naive_utc();
+            let own_host = apikeys::host_uuid
+                .eq(&huuid)
+                .and(apikeys::customer_id.eq(enrollment.cid))
+                .and(apikeys::revoked_at.is_null());
+
+            let usable = own_host.and(
+                apikeys::expires_at
+                    .is_null()
+                    .or(apikeys::expires_at.gt(now)),
+            );
+            if select(exists(dsl_apikeys.filter(usable))).get_result::<bool>(conn)? {
+                return Err(ApiError::InvalidRequestError(Some(String::from(
+                    "enrollment: the host is already enrolled",
+                ))));
+            }
+            // The expired enrolled key would otherwise still hold the unique index
+            update(
+                dsl_apikeys.filter(
+                    own_host
+                        .and(apikeys::enrolled.eq(true))
+                        .and(apikeys::expires_at.le(now)),
+                ),
+            )
+            .set(apikeys::revoked_at.eq(now))
+            .execute(conn)?;
+
+            // Guard against concurrent enrollments using the last use of the token
+            let consumed = update(
+                dsl_enrollments.filter(
+                    id.eq(enrollment.id)
+                        .and(uses.lt(max_uses))
+                        .and(revoked_at.is_null())
+                        .and(expires_at.gt(chrono::Utc::now().naive_utc())),
+                ),
+            )
+            .set(uses.eq(uses + 1))
+            .execute(conn)?;
+            if consumed != 1 {
+                return Err(ApiError::AuthorizationError(Some(String::from(
+                    "the enrollment token has already been used",
+                ))));
+            }
+
+            let generated = GeneratedApiKey::new(verifier);
+            let value = ApiKeyDTO {
+                host_uuid: Some(huuid.clone()),
+                customer_id: Some(enrollment.cid),
+                berta: Some(enrollment.berta.clone()),
+                scopes: Some(vec![ApiKeyScope::Ingest.to_string()]),
+                enrolled: Some(true),
+                ..Default::default()
+            }
+            .with_key(&generated);
+            // The unique index on the enrolled keys catches the concurrent enrollments
+            let apikey: ApiKey = insert_into(dsl_apikeys)
+                .values(&value)
+                .get_result(conn)
+                .map_err(|err| match err {
+                    diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                        ApiError::InvalidRequestError(Some(String::from(
+                            "enrollment: the host is already enrolled",
+                        )))
+                    }
+                    err => ApiError::from(err),
+                })?;
+
+            Ok((apikey, generated))
+        })
+    }
+
+    /// Revoke the token, which can't enroll hosts anymore
+    /// - conn: the Database connection
+    /// - target_id: the id of the token
+    pub fn revoke(conn: &mut ConnType, target_id: i64) -> Result<usize, ApiError> {
+        Ok(
+            update(dsl_enrollments.filter(id.eq(target_id).and(revoked_at.is_null())))
+                .set(revoked_at.eq(chrono::Utc::now().naive_utc()))
+                .execute(conn)?,
+        )
+    }
+
+    /// Delete the token, the hosts it enrolled keep their keys
+    /// - conn: the Database connection
+    /// - target_id: the id of the token
+    pub fn delete(conn: &mut ConnType, target_id: i64) -> Result<usize, ApiError> {
+        Ok(delete(dsl_enrollments.find(target_id)).execute(conn)?)
+    }
+}
+
+impl<'a> BaseCrud<'a> for EnrollmentTokens {
+    type RetType = EnrollmentTokens;
+
+    type VecRetType = Vec<Self::RetType>;
+
+    type TargetType = i64;
+
+    type UuidType = &'a Uuid;
+
+    /// Get all the enrollment tokens of a user
+    /// - conn: the Database connection
+    /// - uuid: the targeted organization's UUID
+    /// - size: how many elements to return
+    /// - page: pagination :shrug:
+    fn get(
+        conn: &mut ConnType,
+        uuid: Self::UuidType,
+        size: i64,
+        page: i64,
+    ) -> Result<Self::VecRetType, ApiError> {
+        Ok(dsl_enrollments
+            .filter(cid.eq(uuid))
+            .limit(size)
+            .offset(page * size)
+            .order_by(created_at.desc())
+            .load(conn)?)
+    }
+
+    /// Get a specific enrollment token depending on the target_id
+    /// - conn: the Database connection
+    /// - target_id: the targeted token's id
+    fn get_specific(
+        conn: &mut ConnType,
+        target_id: Self::TargetType,
+    ) -> Result<Self::RetType, ApiError> {
+        Ok(dsl_enrollments.find(target_id).first(conn)?)
+    }
+}
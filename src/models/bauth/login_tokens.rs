@@ -0,0 +1,35 @@
+use diesel::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::schema::login_tokens;
+
+/// Single use token sent by email (as a link) to log a customer in
+///
+/// Only the SHA-256 hash of the token is stored. The token can only be
+/// used from the IP address which requested it.
+#[derive(Identifiable, Queryable, Debug, Serialize, Deserialize, Clone)]
+#[diesel(table_name = login_tokens)]
+pub struct LoginTokens {
+    pub id: i64,
+    pub cid: Uuid,
+    #[serde(skip)]
+    pub token_hash: String,
+    // IP address of the client which requested the token
+    pub ip: String,
+    pub expires_at: chrono::NaiveDateTime,
+    pub used_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+// ================
+// Insertable model
+// ================
+#[derive(Insertable, Debug)]
+#[diesel(table_name = login_tokens)]
+pub struct LoginTokensDTO {
+    pub cid: Uuid,
+    pub token_hash: String,
+    pub ip: String,
+    pub expires_at: chrono::NaiveDateTime,
+}
@@ -0,0 +1,127 @@
+use actix_session::Session;
+use diesel::*;
+use uuid::Uuid;
+
+use super::apikeys_impl::{hash_secret, random_prefix_and_secret};
+use super::{Customers, LoginOutcome, LoginTokens, LoginTokensDTO};
+use crate::apierrors::ApiError;
+use crate::models::schema::customers::{self, dsl::customers as dsl_customers};
+use crate::models::schema::login_tokens::dsl::{
+    cid, expires_at, ip, login_tokens as dsl_logins, token_hash, used_at,
+};
+use crate::models::InnerUser;
+use crate::ConnType;
+
+/// How many tokens can be waiting to be used for a single customer
+const MAX_PENDING_TOKENS: i64 = 5;
+
+impl LoginTokens {
+    /// Create a new login token for the customer
+    /// - conn: the Database connection
+    /// - ccid: the user's UUID
+    /// - client_ip: the IP address of the client requesting the token
+    /// - ttl: for how long the token can be used
+    ///
+    /// The plaintext token (to send by email) is only returned here. None
+    /// is returned when the customer has too many tokens waiting to be used.
+    pub fn create(
+        conn: &mut ConnType,
+        ccid: &Uuid,
+        client_ip: &str,
+        ttl: chrono::Duration,
+    ) -> Result<Option<String>, ApiError> {
+        conn.transaction(|conn| {
+            // Lock the customer, so concurrent requests can't exceed the limit
+            dsl_customers
+                .find(ccid)
+                .select(customers::id)
+                .for_update()
+                .first::<Uuid>(conn)?;
+
+            let now = chrono::Utc::now().naive_utc();
+            let pending: i64 = dsl_logins
+                .filter(cid.eq(ccid).and(used_at.is_null()).and(expires_at.gt(now)))
+                .count()
+                .get_result(conn)?;
+            if pending >= MAX_PENDING_TOKENS {
+                return Ok(None);
+            }
+
+            let (_, token) = random_prefix_and_secret();
+            let value = LoginTokensDTO {
+                cid: *ccid,
+                token_hash: hash_secret(&token),
+                ip: client_ip.to_owned(),
+                expires_at: now + ttl,
+            };
+            insert_into(dsl_logins).values(&value).execute(conn)?;
+
+            Ok(Some(token))
+        })
+    }
+
+    /// Use the token, returning the customer it belongs to
+    /// - conn: the Database connection
+    /// - token: the token received by email
+    /// - client_ip: the IP address of the client using the token
+    ///
+    /// The token is marked as used in a single statement, so it can only
+    /// be used once. A token used from another IP stays usable.
+    pub fn consume(
+        conn: &mut ConnType,
+        token: &str,
+        client_ip: &str,
+    ) -> Result<Customers, ApiError> {
+        let now = chrono::Utc::now().naive_utc();
+        let login: Option<Self> = update(
+            dsl_logins.filter(
+                token_hash
+                    .eq(hash_secret(token))
+                    .and(used_at.is_null())
+                    .and(expires_at.gt(now))
+                    .and(ip.eq(client_ip)),
+            ),
+        )
+        .set(used_at.eq(now))
+        .get_result(conn)
+        .optional()?;
+
+        match login {
+            Some(login) => Customers::get_by_id(conn, &login.cid),
+            None => Err(ApiError::AuthorizationError(Some(String::from(
+                "the login link is invalid or expired",
+            )))),
+        }
+    }
+
+    /// Use the token and log its customer in the session
+    /// - conn: the Database connection
+    /// - session: the session of the client
+    /// - token: the token received by email
+    /// - client_ip: the IP address of the client using the token
+    ///
+    /// The customers with the second factor enabled still have to pass it.
+    pub fn verify(
+        conn: &mut ConnType,
+        session: &Session,
+        token: &str,
+        client_ip: &str,
+    ) -> Result<LoginOutcome, ApiError> {
+        let customer = Self::consume(conn, token, client_ip)?;
+
+        InnerUser::login(session, &customer)
+    }
+
+    /// Delete the tokens which can't be used anymore
+    /// - conn: the Database connection
+    pub fn delete_expired(conn: &mut ConnType) -> Result<usize, ApiError> {
+        Ok(delete(
+            dsl_logins.filter(
+                expires_at
+                    .le(chrono::Utc::now().naive_utc())
+                    .or(used_at.is_not_null()),
+            ),
+        )
+        .execute(conn)?)
+    }
+}
@@ -1,7 +1,33 @@
 mod apikeys;
 mod apikeys_impl;
+mod apikeys_signing;
 pub use apikeys::*;
+pub use apikeys_signing::*;
 
 mod customers;
 mod customers_impl;
 pub use customers::*;
+
+mod enrollment_tokens;
+mod enrollment_tokens_impl;
+pub use enrollment_tokens::*;
+
+mod organizations;
+pub(crate) mod organizations_impl;
+pub use organizations::*;
+
+mod roles;
+mod roles_impl;
+pub use roles::*;
+pub use roles_impl::{authorize, get_role};
+
+mod login_tokens;
+mod login_tokens_impl;
+pub use login_tokens::*;
+
+mod sessions;
+pub use sessions::*;
+
+mod totp;
+mod totp_impl;
+pub use totp::*;
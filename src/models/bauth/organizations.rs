@@ -0,0 +1,91 @@
+use diesel::*;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::models::schema::{organization_invitations, organizations};
+
+/// Organization owning the resources (hosts, Api Keys, alerts, incidents, ...)
+///
+/// The cid/customer_id of the resources hold the id of their organization.
+/// Every customer has a personal organization sharing its id.
+#[derive(Identifiable, Queryable, Debug, Serialize, Deserialize, Clone, TS)]
+#[diesel(table_name = organizations)]
+#[ts(export)]
+pub struct Organizations {
+    pub id: Uuid,
+    #[diesel(column_name = _name)]
+    pub name: String,
+    // Created along with its customer, can't be deleted nor left by it
+    pub personal: bool,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+// ================
+// Insertable model
+// ================
+#[derive(Insertable, Deserialize, Serialize, Debug, TS)]
+#[diesel(table_name = organizations)]
+#[ts(export)]
+pub struct OrganizationsDTO {
+    #[diesel(column_name = _name)]
+    pub name: String,
+}
+
+/// Membership of a customer to an organization
+#[derive(Queryable, Debug, Serialize, Deserialize, Clone, TS)]
+#[diesel(table_name = organization_members)]
+#[ts(export)]
+pub struct OrganizationMembers {
+    pub org_id: Uuid,
+    pub customer_id: Uuid,
+    pub created_at: chrono::NaiveDateTime,
+    // What the member is allowed to do (see Role)
+    pub role: String,
+}
+
+/// Invitation of an email address to join an organization
+///
+/// Only the SHA-256 hash of the token sent by email is stored.
+#[derive(Identifiable, Queryable, Debug, Serialize, Deserialize, Clone, TS)]
+#[diesel(table_name = organization_invitations)]
+#[ts(export)]
+pub struct OrganizationInvitations {
+    #[ts(type = "number")]
+    pub id: i64,
+    pub org_id: Uuid,
+    pub email: String,
+    #[serde(skip)]
+    pub token_hash: String,
+    // The member who sent the invitation
+    pub invited_by: Uuid,
+    pub expires_at: chrono::NaiveDateTime,
+    pub accepted_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    // Role given to the customer accepting the invitation
+    pub role: String,
+}
+
+// ================
+// Insertable model
+// ================
+#[derive(Insertable, Debug)]
+#[diesel(table_name = organization_invitations)]
+pub struct OrganizationInvitationsDTO {
+    pub org_id: Uuid,
+    pub email: String,
+    pub token_hash: String,
+    pub invited_by: Uuid,
+    pub expires_at: chrono::NaiveDateTime,
+    pub role: String,
+}
+
+/// Newly created invitation, the only time the plaintext token is known
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct GeneratedInvitation {
+    #[serde(flatten)]
+    pub invitation: OrganizationInvitations,
+    // Token to send to the invited address, needed to accept the invitation
+    pub token: String,
+}
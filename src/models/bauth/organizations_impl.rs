@@ -0,0 +1,551 @@
+use diesel::dsl::exists;
+use diesel::*;
+use uuid::Uuid;
+
+use super::apikeys_impl::{hash_secret, random_prefix_and_secret};
+use super::{
+    authorize, get_role, Action, Customers, EmailKind, GeneratedInvitation,
+    NotificationPreferences, OrganizationInvitations, OrganizationInvitationsDTO,
+    OrganizationMembers, Organizations, OrganizationsDTO, Resource, Role,
+};
+use crate::apierrors::ApiError;
+use crate::models::is_valid_email;
+use crate::models::schema::{
+    apikeys, customers, enrollment_tokens, organization_invitations, organization_members,
+    organizations,
+};
+use crate::ConnType;
+
+/// Query selecting the ids of the organizations the customer is a member of
+pub(crate) type OrgIdsOf<'a> = dsl::Select<
+    dsl::Filter<organization_members::table, dsl::Eq<organization_members::customer_id, &'a Uuid>>,
+    organization_members::org_id,
+>;
+
+/// Ids of the organizations the customer is a member of, to be used as
+/// a subselect (eg: `customer_id.eq_any(org_ids_of(cid))`) by the ownership checks
+pub(crate) fn org_ids_of(cid: &Uuid) -> OrgIdsOf<'_> {
+    organization_members::table
+        .filter(organization_members::customer_id.eq(cid))
+        .select(organization_members::org_id)
+}
+
+/// Get the members (and their role) of the organization, locking them
+/// until the end of the transaction
+fn lock_members(conn: &mut ConnType, oid: &Uuid) -> Result<Vec<(Uuid, Role)>, ApiError> {
+    organization_members::table
+        .filter(organization_members::org_id.eq(oid))
+        .select((
+            organization_members::customer_id,
+            organization_members::role,
+        ))
+        .for_update()
+        .load::<(Uuid, String)>(conn)?
+        .into_iter()
+        .map(|(member, member_role)| Ok((member, member_role.parse::<Role>()?)))
+        .collect()
+}
+
+/// Assert that the actor is allowed to give (or take away) the role
+fn assert_can_manage(
+    conn: &mut ConnType,
+    oid: &Uuid,
+    actor: &Uuid,
+    target: Role,
+) -> Result<(), ApiError> {
+    let actor_role = authorize(conn, actor, Action::Write, &Resource::Members(*oid))?;
+    if !actor_role.can_manage(target) {
+        return Err(ApiError::AuthorizationError(Some(format!(
+            "the {} role is not allowed to manage the {} role",
+            actor_role, target
+        ))));
+    }
+
+    Ok(())
+}
+
+/// Assert that the organization keeps an owner once the member lose its role
+fn assert_other_owner(members: &[(Uuid, Role)], cid: &Uuid, current: Role) -> Result<(), ApiError> {
+    if current == Role::Owner
+        && !members
+            .iter()
+            .any(|(member, member_role)| member != cid && *member_role == Role::Owner)
+    {
+        return Err(ApiError::InvalidRequestError(Some(String::from(
+            "organization: at least one owner is needed",
+        ))));
+    }
+
+    Ok(())
+}
+
+/// Revoke the Api Keys and enrollment tokens of the organization, before deleting it
+/// - conn: the Database connection
+/// - oid: the organization's UUID
+pub(crate) fn revoke_credentials(conn: &mut ConnType, oid: &Uuid) -> Result<(), ApiError> {
+    let now = chrono::Utc::now().naive_utc();
+    update(
+        apikeys::table.filter(
+            apikeys::customer_id
+                .eq(oid)
+                .and(apikeys::revoked_at.is_null()),
+        ),
+    )
+    .set(apikeys::revoked_at.eq(now))
+    .execute(conn)?;
+    update(
+        enrollment_tokens::table.filter(
+            enrollment_tokens::cid
+                .eq(oid)
+                .and(enrollment_tokens::revoked_at.is_null()),
+        ),
+    )
+    .set(enrollment_tokens::revoked_at.eq(now))
+    .execute(conn)?;
+
+    Ok(())
+}
+
+/// Assert that the customer is not the last owner of a shared organization,
+/// which would be left without owner once the customer is deleted
+/// - conn: the Database connection
+/// - cid: the user's UUID
+pub(crate) fn assert_not_last_owner(conn: &mut ConnType, cid: &Uuid) -> Result<(), ApiError> {
+    let owned: Vec<Uuid> = organization_members::table
+        .filter(
+            organization_members::customer_id
+                .eq(cid)
+                .and(organization_members::role.eq(Role::Owner.as_str()))
+                .and(
+                    organization_members::org_id.eq_any(
+                        organizations::table
+                            .filter(organizations::personal.eq(false))
+                            .select(organizations::id),
+                    ),
+                ),
+        )
+        .select(organization_members::org_id)
+        .load(conn)?;
+
+    for oid in owned {
+        let members = lock_members(conn, &oid)?;
+        assert_other_owner(&members, cid, Role::Owner).map_err(|_| {
+            ApiError::InvalidRequestError(Some(String::from(
+                "customer: transfer the ownership of your organizations before deleting your account",
+            )))
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Assert that the name of the organization is usable
+fn validate_name(name: &str) -> Result<(), ApiError> {
+    if name.trim().is_empty() || name.len() > 128 {
+        return Err(ApiError::InvalidRequestError(Some(String::from(
+            "organization: name must be 1 to 128 characters",
+        ))));
+    }
+
+    Ok(())
+}
+
+impl Organizations {
+    /// Create a new organization with the customer as its first owner
+    /// - conn: the Database connection
+    /// - value: the organization to create
+    /// - creator: the customer creating it
+    pub fn create(
+        conn: &mut ConnType,
+        value: &OrganizationsDTO,
+        creator: &Uuid,
+    ) -> Result<Self, ApiError> {
+        validate_name(&value.name)?;
+
+        conn.transaction(|conn| {
+            let org: Self = insert_into(organizations::table)
+                .values(value)
+                .get_result(conn)?;
+            insert_into(organization_members::table)
+                .values((
+                    organization_members::org_id.eq(org.id),
+                    organization_members::customer_id.eq(creator),
+                    organization_members::role.eq(Role::Owner.as_str()),
+                ))
+                .execute(conn)?;
+
+            Ok(org)
+        })
+    }
+
+    /// Create the personal organization of a (new) customer
+    /// - conn: the Database connection
+    /// - customer: the customer owning it
+    ///
+    /// The organization shares the id of the customer.
+    pub fn create_personal(conn: &mut ConnType, customer: &Customers) -> Result<Self, ApiError> {
+        let org: Self = insert_into(organizations::table)
+            .values((
+                organizations::id.eq(customer.id),
+                organizations::_name.eq(&customer.email),
+                organizations::personal.eq(true),
+            ))
+            .get_result(conn)?;
+        insert_into(organization_members::table)
+            .values((
+                organization_members::org_id.eq(customer.id),
+                organization_members::customer_id.eq(customer.id),
+                organization_members::role.eq(Role::Owner.as_str()),
+            ))
+            .execute(conn)?;
+
+        Ok(org)
+    }
+
+    /// Get a specific organization
+    /// - conn: the Database connection
+    /// - oid: the organization's UUID
+    pub fn get_specific(conn: &mut ConnType, oid: &Uuid) -> Result<Self, ApiError> {
+        Ok(organizations::table.find(oid).first(conn)?)
+    }
+
+    /// Get the organizations the customer is a member of
+    /// - conn: the Database connection
+    /// - cid: the user's UUID
+    pub fn get_by_member(conn: &mut ConnType, cid: &Uuid) -> Result<Vec<Self>, ApiError> {
+        Ok(organizations::table
+            .filter(organizations::id.eq_any(org_ids_of(cid)))
+            .order_by((organizations::personal.desc(), organizations::_name.asc()))
+            .load(conn)?)
+    }
+
+    /// Get the ids of the organizations the customer is a member of
+    /// - conn: the Database connection
+    /// - cid: the user's UUID
+    ///
+    /// Meant for the ownership checks of the resources stored
+    /// in another database (alerts, incidents, ...).
+    pub fn get_ids_by_member(conn: &mut ConnType, cid: &Uuid) -> Result<Vec<Uuid>, ApiError> {
+        Ok(org_ids_of(cid).load(conn)?)
+    }
+
+    /// Is the customer a member of the organization
+    /// - conn: the Database connection
+    /// - oid: the organization's UUID
+    /// - cid: the user's UUID
+    pub fn is_member(conn: &mut ConnType, oid: &Uuid, cid: &Uuid) -> Result<bool, ApiError> {
+        Ok(select(exists(
+            organization_members::table.filter(
+                organization_members::org_id
+                    .eq(oid)
+                    .and(organization_members::customer_id.eq(cid)),
+            ),
+        ))
+        .get_result(conn)?)
+    }
+
+    /// Get the members of the organization
+    /// - conn: the Database connection
+    /// - oid: the organization's UUID
+    pub fn get_members(
+        conn: &mut ConnType,
+        oid: &Uuid,
+    ) -> Result<Vec<OrganizationMembers>, ApiError> {
+        Ok(organization_members::table
+            .filter(organization_members::org_id.eq(oid))
+            .order_by(organization_members::created_at.asc())
+            .load(conn)?)
+    }
+
+    /// Get the email addresses of the members of the organization
+    /// - conn: the Database connection
+    /// - oid: the organization's UUID
+    pub fn get_member_emails(conn: &mut ConnType, oid: &Uuid) -> Result<Vec<String>, ApiError> {
+        Ok(organization_members::table
+            .inner_join(customers::table)
+            .filter(organization_members::org_id.eq(oid))
+            .select(customers::email)
+            .order_by(organization_members::created_at.asc())
+            .load(conn)?)
+    }
+
+    /// Get the email addresses of the members wanting this kind of emails
+    /// - conn: the Database connection
+    /// - oid: the organization's UUID
+    /// - kind: the kind of the emails, checked against the NotificationPreferences
+    pub fn get_notified_emails(
+        conn: &mut ConnType,
+        oid: &Uuid,
+        kind: EmailKind,
+    ) -> Result<Vec<String>, ApiError> {
+        Ok(organization_members::table
+            .inner_join(customers::table)
+            .filter(organization_members::org_id.eq(oid))
+            .select((customers::email, customers::notification_prefs))
+            .order_by(organization_members::created_at.asc())
+            .load::<(String, NotificationPreferences)>(conn)?
+            .into_iter()
+            .filter(|(_, prefs)| prefs.allows(kind))
+            .map(|(email, _)| email)
+            .collect())
+    }
+
+    /// Remove the customer from the organization
+    /// - conn: the Database connection
+    /// - oid: the organization's UUID
+    /// - actor: the member removing the customer (the customer itself to leave)
+    /// - cid: the user's UUID
+    ///
+    /// The owner of a personal organization can't leave it, and an
+    /// organization always keeps at least one owner.
+    pub fn remove_member(
+        conn: &mut ConnType,
+        oid: &Uuid,
+        actor: &Uuid,
+        cid: &Uuid,
+    ) -> Result<usize, ApiError> {
+        if oid == cid {
+            return Err(ApiError::InvalidRequestError(Some(String::from(
+                "organization: the owner of a personal organization can't leave it",
+            ))));
+        }
+
+        conn.transaction(|conn| {
+            let members = lock_members(conn, oid)?;
+            let removed = match members.iter().find(|(member, _)| member == cid) {
+                Some((_, removed)) => *removed,
+                None => return Ok(0),
+            };
+            if actor != cid {
+                assert_can_manage(conn, oid, actor, removed)?;
+            }
+            assert_other_owner(&members, cid, removed)?;
+
+            Ok(delete(organization_members::table.find((oid, cid))).execute(conn)?)
+        })
+    }
+
+    /// Change the role of a member of the organization
+    /// - conn: the Database connection
+    /// - oid: the organization's UUID
+    /// - actor: the member changing the role
+    /// - cid: the user's UUID
+    /// - new_role: the role to give
+    ///
+    /// The actor must be able to manage both the current and the new role,
+    /// and the organization always keeps at least one owner.
+    pub fn set_role(
+        conn: &mut ConnType,
+        oid: &Uuid,
+        actor: &Uuid,
+        cid: &Uuid,
+        new_role: Role,
+    ) -> Result<usize, ApiError> {
+        conn.transaction(|conn| {
+            let members = lock_members(conn, oid)?;
+            let current = match members.iter().find(|(member, _)| member == cid) {
+                Some((_, current)) => *current,
+                None => return Err(ApiError::DieselError(diesel::result::Error::NotFound)),
+            };
+            assert_can_manage(conn, oid, actor, current.max(new_role))?;
+            if new_role != Role::Owner {
+                if oid == cid {
+                    return Err(ApiError::InvalidRequestError(Some(String::from(
+                        "organization: the owner of a personal organization must stay its owner",
+                    ))));
+                }
+                assert_other_owner(&members, cid, current)?;
+            }
+
+            Ok(update(organization_members::table.find((oid, cid)))
+                .set(organization_members::role.eq(new_role.as_str()))
+                .execute(conn)?)
+        })
+    }
+
+    /// Rename the organization
+    /// - conn: the Database connection
+    /// - oid: the organization's UUID
+    /// - actor: the member renaming it
+    /// - name: the new name
+    pub fn rename(
+        conn: &mut ConnType,
+        oid: &Uuid,
+        actor: &Uuid,
+        name: &str,
+    ) -> Result<Self, ApiError> {
+        validate_name(name)?;
+        authorize(conn, actor, Action::Write, &Resource::Members(*oid))?;
+
+        Ok(update(organizations::table.find(oid))
+            .set(organizations::_name.eq(name))
+            .get_result(conn)?)
+    }
+
+    /// Delete the organization, along with its memberships and invitations
+    /// - conn: the Database connection
+    /// - oid: the organization's UUID
+    /// - actor: the member deleting it, who must be an owner
+    ///
+    /// Its Api Keys and enrollment tokens are revoked, so no agent can keep
+    /// sending data for it. The other resources are not deleted here.
+    pub fn delete(conn: &mut ConnType, oid: &Uuid, actor: &Uuid) -> Result<usize, ApiError> {
+        if get_role(conn, oid, actor)? != Some(Role::Owner) {
+            return Err(ApiError::AuthorizationError(Some(String::from(
+                "only the owners can delete the organization",
+            ))));
+        }
+
+        conn.transaction(|conn| {
+            let deleted = delete(
+                organizations::table.filter(
+                    organizations::id
+                        .eq(oid)
+                        .and(organizations::personal.eq(false)),
+                ),
+            )
+            .execute(conn)?;
+            if deleted > 0 {
+                revoke_credentials(conn, oid)?;
+            }
+
+            Ok(deleted)
+        })
+    }
+}
+
+impl OrganizationInvitations {
+    /// Invite an email address to join the organization
+    /// - conn: the Database connection
+    /// - oid: the organization's UUID
+    /// - invited_email: the email address to invite
+    /// - invited_by: the member sending the invitation
+    /// - invited_role: the role given when accepting the invitation
+    /// - ttl: for how long the invitation can be accepted
+    ///
+    /// The plaintext token (to send by email) is only returned here.
+    pub fn create(
+        conn: &mut ConnType,
+        oid: &Uuid,
+        invited_email: &str,
+        invited_by: &Uuid,
+        invited_role: Role,
+        ttl: chrono::Duration,
+    ) -> Result<GeneratedInvitation, ApiError> {
+        if !is_valid_email(invited_email) {
+            return Err(ApiError::InvalidRequestError(Some(format!(
+                "organization: {} is not a valid email address",
+                invited_email
+            ))));
+        }
+        assert_can_manage(conn, oid, invited_by, invited_role)?;
+        if Organizations::get_member_emails(conn, oid)?
+            .iter()
+            .any(|member| member.eq_ignore_ascii_case(invited_email))
+        {
+            return Err(ApiError::InvalidRequestError(Some(String::from(
+                "organization: the email address is already a member",
+            ))));
+        }
+
+        let (_, token) = random_prefix_and_secret();
+        let value = OrganizationInvitationsDTO {
+            org_id: *oid,
+            email: invited_email.to_owned(),
+            token_hash: hash_secret(&token),
+            invited_by: *invited_by,
+            expires_at: chrono::Utc::now().naive_utc() + ttl,
+            role: invited_role.to_string(),
+        };
+
+        Ok(GeneratedInvitation {
+            invitation: insert_into(organization_invitations::table)
+                .values(&value)
+                .get_result(conn)?,
+            token,
+        })
+    }
+
+    /// Get the invitations of the organization not accepted yet
+    /// - conn: the Database connection
+    /// - oid: the organization's UUID
+    /// - actor: the member listing them, who must be allowed to manage the members
+    pub fn get_pending(
+        conn: &mut ConnType,
+        oid: &Uuid,
+        actor: &Uuid,
+    ) -> Result<Vec<Self>, ApiError> {
+        authorize(conn, actor, Action::Write, &Resource::Members(*oid))?;
+
+        Ok(organization_invitations::table
+            .filter(
+                organization_invitations::org_id
+                    .eq(oid)
+                    .and(organization_invitations::accepted_at.is_null()),
+            )
+            .order_by(organization_invitations::created_at.desc())
+            .load(conn)?)
+    }
+
+    /// Accept the invitation, making the customer a member of the organization
+    /// - conn: the Database connection
+    /// - token: the token sent to the invited address
+    /// - cid: the user's UUID
+    ///
+    /// The invitation can only be accepted once, by the customer using the invited address.
+    pub fn accept(conn: &mut ConnType, token: &str, cid: &Uuid) -> Result<Organizations, ApiError> {
+        let refused = || {
+            ApiError::AuthorizationError(Some(String::from("the invitation is invalid or expired")))
+        };
+
+        conn.transaction(|conn| {
+            let customer = Customers::get_by_id(conn, cid)?;
+            let invitation: Self = update(
+                organization_invitations::table.filter(
+                    organization_invitations::token_hash
+                        .eq(hash_secret(token))
+                        .and(organization_invitations::accepted_at.is_null())
+                        .and(
+                            organization_invitations::expires_at.gt(chrono::Utc::now().naive_utc()),
+                        ),
+                ),
+            )
+            .set(organization_invitations::accepted_at.eq(chrono::Utc::now().naive_utc()))
+            .get_result(conn)
+            .optional()?
+            .ok_or_else(refused)?;
+
+            // Rolled back along with the transaction, so the invitation stays usable
+            if !invitation.email.eq_ignore_ascii_case(&customer.email) {
+                return Err(refused());
+            }
+
+            insert_into(organization_members::table)
+                .values((
+                    organization_members::org_id.eq(invitation.org_id),
+                    organization_members::customer_id.eq(cid),
+                    organization_members::role.eq(&invitation.role),
+                ))
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+
+            Organizations::get_specific(conn, &invitation.org_id)
+        })
+    }
+
+    /// Delete the invitation, which can't be accepted anymore
+    /// - conn: the Database connection
+    /// - iid: the id of the invitation
+    /// - actor: the member deleting it
+    pub fn delete(conn: &mut ConnType, iid: i64, actor: &Uuid) -> Result<usize, ApiError> {
+        let invitation: Self = organization_invitations::table.find(iid).first(conn)?;
+        authorize(
+            conn,
+            actor,
+            Action::Delete,
+            &Resource::Members(invitation.org_id),
+        )?;
+
+        Ok(delete(organization_invitations::table.find(iid)).execute(conn)?)
+    }
+}
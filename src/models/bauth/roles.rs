@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+/// Role of a member inside of an organization
+///
+/// Stored as a string in the role column of the memberships. The
+/// variants are ordered from the least to the most privileged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum Role {
+    Viewer,
+    Editor,
+    Admin,
+    Owner,
+}
+
+/// Operation a member wants to perform on a resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum Action {
+    Read,
+    // Create or update
+    Write,
+    Delete,
+}
+
+/// Resource targeted by an operation, along with the organization owning it
+///
+/// For an existing resource (eg: an alert), the organization is its cid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Alerts(Uuid),
+    Incidents(Uuid),
+    Hosts(Uuid),
+    ApiKeys(Uuid),
+    Billing(Uuid),
+    // The organization itself, its members and invitations
+    Members(Uuid),
+}
@@ -0,0 +1,252 @@
+use std::fmt;
+use std::str::FromStr;
+
+use diesel::*;
+use uuid::Uuid;
+
+use super::{Action, Resource, Role};
+use crate::apierrors::ApiError;
+use crate::models::schema::organization_members::dsl::{
+    customer_id, org_id, organization_members as dsl_members, role,
+};
+use crate::models::InnerUser;
+use crate::ConnType;
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+            Role::Owner => "owner",
+        }
+    }
+
+    /// Permission matrix of the roles
+    ///
+    /// | resource  | viewer | editor              | admin               | owner               |
+    /// |-----------|--------|---------------------|---------------------|---------------------|
+    /// | alerts    | read   | read, write, delete | read, write, delete | read, write, delete |
+    /// | incidents | read   | read, write         | read, write, delete | read, write, delete |
+    /// | hosts     | read   | read, write         | read, write, delete | read, write, delete |
+    /// | api keys  | -      | read                | read, write, delete | read, write, delete |
+    /// | billing   | -      | -                   | read                | read, write, delete |
+    /// | members   | read   | read                | read, write, delete | read, write, delete |
+    pub fn allows(&self, action: Action, resource: &Resource) -> bool {
+        match (self, resource) {
+            (Role::Owner, _) => true,
+            (Role::Admin, Resource::Billing(_)) => action == Action::Read,
+            (Role::Admin, _) => true,
+            (Role::Editor, Resource::Alerts(_)) => true,
+            (Role::Editor, Resource::Incidents(_) | Resource::Hosts(_)) => action != Action::Delete,
+            (Role::Editor, Resource::ApiKeys(_) | Resource::Members(_)) => action == Action::Read,
+            (
+                Role::Viewer,
+                Resource::Alerts(_)
+                | Resource::Incidents(_)
+                | Resource::Hosts(_)
+                | Resource::Members(_),
+            ) => action == Action::Read,
+            (Role::Editor | Role::Viewer, _) => false,
+        }
+    }
+
+    /// Can a member with this role give (or take away) the other role
+    ///
+    /// Only the owners can manage the owners.
+    pub fn can_manage(&self, other: Role) -> bool {
+        *self >= Role::Admin && *self >= other
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "editor" => Ok(Role::Editor),
+            "admin" => Ok(Role::Admin),
+            "owner" => Ok(Role::Owner),
+            _ => Err(ApiError::InvalidRequestError(Some(format!(
+                "role: {} is invalid. Valid are: viewer, editor, admin, owner.",
+                s
+            )))),
+        }
+    }
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Read => "read",
+            Action::Write => "write",
+            Action::Delete => "delete",
+        }
+    }
+}
+
+impl Resource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resource::Alerts(_) => "alerts",
+            Resource::Incidents(_) => "incidents",
+            Resource::Hosts(_) => "hosts",
+            Resource::ApiKeys(_) => "api keys",
+            Resource::Billing(_) => "billing",
+            Resource::Members(_) => "members",
+        }
+    }
+
+    /// The organization owning the resource
+    pub fn org(&self) -> &Uuid {
+        match self {
+            Resource::Alerts(oid)
+            | Resource::Incidents(oid)
+            | Resource::Hosts(oid)
+            | Resource::ApiKeys(oid)
+            | Resource::Billing(oid)
+            | Resource::Members(oid) => oid,
+        }
+    }
+}
+
+/// Get the role of the customer in the organization (None if not a member)
+/// - conn: the Database connection
+/// - oid: the organization's UUID
+/// - cid: the user's UUID
+pub fn get_role(conn: &mut ConnType, oid: &Uuid, cid: &Uuid) -> Result<Option<Role>, ApiError> {
+    dsl_members
+        .select(role)
+        .filter(org_id.eq(oid).and(customer_id.eq(cid)))
+        .first::<String>(conn)
+        .optional()?
+        .map(|r| r.parse::<Role>())
+        .transpose()
+}
+
+/// Assert that the user is allowed to perform the action on the resource
+/// - conn: the Database connection
+/// - user: the user's UUID
+/// - action: the operation to perform
+/// - resource: the targeted resource (and its organization)
+///
+/// This is the check the services (actix and axum) must perform before
+/// each operation done on behalf of a user. Return the role of the user
+/// in the organization, or an AuthorizationError holding the reason.
+pub fn authorize(
+    conn: &mut ConnType,
+    user: &Uuid,
+    action: Action,
+    resource: &Resource,
+) -> Result<Role, ApiError> {
+    let member_role = match get_role(conn, resource.org(), user)? {
+        Some(member_role) => member_role,
+        None => {
+            return Err(ApiError::AuthorizationError(Some(String::from(
+                "you are not a member of the organization owning the resource",
+            ))))
+        }
+    };
+
+    if !member_role.allows(action, resource) {
+        return Err(ApiError::AuthorizationError(Some(format!(
+            "the {} role is not allowed to {} the {}",
+            member_role,
+            action.as_str(),
+            resource.as_str()
+        ))));
+    }
+
+    Ok(member_role)
+}
+
+impl InnerUser {
+    /// Assert that the user is allowed to perform the action on the resource
+    ///
+    /// Same as authorize, for the user extracted from the session.
+    pub fn authorize(
+        &self,
+        conn: &mut ConnType,
+        action: Action,
+        resource: &Resource,
+    ) -> Result<Role, ApiError> {
+        authorize(conn, &self.uuid, action, resource)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROLES: [Role; 4] = [Role::Viewer, Role::Editor, Role::Admin, Role::Owner];
+    const ACTIONS: [Action; 3] = [Action::Read, Action::Write, Action::Delete];
+
+    /// The documented matrix: the allowed actions of each role, in ROLES order
+    fn matrix(oid: Uuid) -> Vec<(Resource, [&'static str; 4])> {
+        let all = "read, write, delete";
+        vec![
+            (Resource::Alerts(oid), ["read", all, all, all]),
+            (Resource::Incidents(oid), ["read", "read, write", all, all]),
+            (Resource::Hosts(oid), ["read", "read, write", all, all]),
+            (Resource::ApiKeys(oid), ["-", "read", all, all]),
+            (Resource::Billing(oid), ["-", "-", "read", all]),
+            (Resource::Members(oid), ["read", "read", all, all]),
+        ]
+    }
+
+    #[test]
+    fn allows_follows_the_matrix() {
+        for (resource, allowed) in matrix(Uuid::new_v4()) {
+            for (member_role, allowed) in ROLES.iter().zip(allowed) {
+                for action in ACTIONS {
+                    let expected = allowed.split(", ").any(|a| a == action.as_str());
+                    assert_eq!(
+                        member_role.allows(action, &resource),
+                        expected,
+                        "{} {} the {}",
+                        member_role,
+                        action.as_str(),
+                        resource.as_str()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn only_admins_and_owners_manage() {
+        for other in ROLES {
+            assert!(!Role::Viewer.can_manage(other));
+            assert!(!Role::Editor.can_manage(other));
+            assert!(Role::Owner.can_manage(other));
+        }
+        assert!(Role::Admin.can_manage(Role::Viewer));
+        assert!(Role::Admin.can_manage(Role::Editor));
+        assert!(Role::Admin.can_manage(Role::Admin));
+    }
+
+    #[test]
+    fn only_owners_manage_owners() {
+        for member_role in ROLES {
+            assert_eq!(
+                member_role.can_manage(Role::Owner),
+                member_role == Role::Owner
+            );
+        }
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        for member_role in ROLES {
+            assert_eq!(member_role.as_str().parse::<Role>().unwrap(), member_role);
+        }
+        assert!("root".parse::<Role>().is_err());
+    }
+}
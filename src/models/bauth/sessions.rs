@@ -0,0 +1,90 @@
+use actix_session::Session;
+use uuid::Uuid;
+
+use super::Customers;
+use crate::apierrors::ApiError;
+use crate::models::InnerUser;
+use crate::ConnType;
+
+/// Key of the session (see get_session_middleware) holding the UUID of the user
+pub const SESSION_USER_KEY: &str = "user_id";
+/// Key of the session holding the UUID of the user while its second factor is expected
+///
+/// Kept apart from SESSION_USER_KEY, so a pending session is never logged in.
+pub const SESSION_PENDING_USER_KEY: &str = "pending_user_id";
+
+/// Result of a login, which may still need the second factor
+#[derive(Debug)]
+pub enum LoginOutcome {
+    Authenticated(InnerUser),
+    // See InnerUser::verify_second_factor
+    SecondFactorRequired,
+}
+
+impl InnerUser {
+    /// Get the user logged in the session
+    ///
+    /// A user who still has to pass the second factor is not logged in.
+    pub fn from_session(session: &Session) -> Result<Self, ApiError> {
+        match session.get::<Uuid>(SESSION_USER_KEY)? {
+            Some(uuid) => Ok(InnerUser { uuid }),
+            None if session.get::<Uuid>(SESSION_PENDING_USER_KEY)?.is_some() => Err(
+                ApiError::SessionError(Some(String::from("the second factor is required"))),
+            ),
+            None => Err(ApiError::SessionError(Some(String::from(
+                "you are not logged in",
+            )))),
+        }
+    }
+
+    /// Log the user in the session
+    ///
+    /// The session is renewed first, so a session created
+    /// before the login can't be reused (session fixation).
+    /// With the second factor enabled, the session stays pending
+    /// until it is verified.
+    pub fn login(session: &Session, customer: &Customers) -> Result<LoginOutcome, ApiError> {
+        session.renew();
+
+        if customer.totp_enabled_at.is_some() {
+            session.insert(SESSION_PENDING_USER_KEY, customer.id)?;
+            return Ok(LoginOutcome::SecondFactorRequired);
+        }
+
+        session.insert(SESSION_USER_KEY, customer.id)?;
+        Ok(LoginOutcome::Authenticated(InnerUser { uuid: customer.id }))
+    }
+
+    /// Complete the login of a pending session with the second factor
+    /// - conn: the Database connection
+    /// - session: the session of the client
+    /// - code: the code of the authenticator, or one of the recovery codes
+    pub fn verify_second_factor(
+        conn: &mut ConnType,
+        session: &Session,
+        code: &str,
+    ) -> Result<Self, ApiError> {
+        let uuid = match session.get::<Uuid>(SESSION_PENDING_USER_KEY)? {
+            Some(uuid) => uuid,
+            // Already logged in, nothing left to verify
+            None => return Self::from_session(session),
+        };
+
+        if !Customers::verify_second_factor(conn, &uuid, code)? {
+            return Err(ApiError::AuthorizationError(Some(String::from(
+                "the code is invalid",
+            ))));
+        }
+
+        session.renew();
+        session.remove(SESSION_PENDING_USER_KEY);
+        session.insert(SESSION_USER_KEY, uuid)?;
+
+        Ok(InnerUser { uuid })
+    }
+
+    /// Log the user out, removing everything from the session
+    pub fn logout(session: &Session) {
+        session.purge();
+    }
+}
@@ -0,0 +1,29 @@
+use diesel::*;
+use serde::Serialize;
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::models::schema::recovery_codes;
+
+/// One-time code to pass the second factor when the authenticator is lost
+///
+/// Only the SHA-256 hash of the code is stored.
+#[derive(Identifiable, Queryable, Debug)]
+#[diesel(table_name = recovery_codes)]
+pub struct RecoveryCodes {
+    pub id: i64,
+    pub cid: Uuid,
+    pub code_hash: String,
+    pub used_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// TOTP authenticator being enrolled, to display to the customer
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct TotpEnrollment {
+    // Base32 secret, for the manual entry in the authenticator
+    pub secret: String,
+    // otpauth:// URI, to display as a QR code
+    pub uri: String,
+}
@@ -0,0 +1,462 @@
+use diesel::*;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use uuid::Uuid;
+
+use super::apikeys_impl::{constant_time_eq, hash_secret};
+use super::{Customers, RecoveryCodes, TotpEnrollment};
+use crate::apierrors::ApiError;
+use crate::models::schema::customers::dsl::{
+    customers as dsl_customers, id, totp_enabled_at, totp_failures, totp_last_step,
+    totp_locked_until, totp_secret,
+};
+use crate::models::schema::recovery_codes::dsl::{
+    cid, code_hash, recovery_codes as dsl_recovery, used_at,
+};
+use crate::ConnType;
+
+/// Number of digits of the codes
+const TOTP_DIGITS: u32 = 6;
+/// Duration (in seconds) of a time step
+const TOTP_PERIOD: i64 = 30;
+/// Number of steps accepted before and after the current one (clock drift)
+const TOTP_DRIFT: i64 = 1;
+/// Failed attempts after which the second factor is locked
+const MAX_FAILURES: i32 = 5;
+/// For how long (in seconds) the second factor stays locked
+const LOCK_DURATION: i64 = 15 * 60;
+/// Number of recovery codes generated at once
+const RECOVERY_CODES_COUNT: usize = 10;
+
+/// Encode the bytes in base32 (RFC 4648, without padding) as the authenticators expect
+fn base32_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    let mut encoded = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            encoded.push(ALPHABET[((buffer >> (bits - 5)) & 31) as usize] as char);
+            bits -= 5;
+        }
+    }
+    if bits > 0 {
+        encoded.push(ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+
+    encoded
+}
+
+/// Percent-encode everything but the unreserved characters (RFC 3986)
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// HOTP value (RFC 4226) of the counter, truncated to TOTP_DIGITS digits
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    // HMAC accept keys of any size, new_from_slice cannot fail
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).unwrap();
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    binary % 10u32.pow(TOTP_DIGITS)
+}
+
+/// Get the time step (within the drift window) for which the code is valid
+fn matching_step(key: &[u8], code: &str, now: i64) -> Option<i64> {
+    if code.len() != TOTP_DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let current = now.div_euclid(TOTP_PERIOD);
+    (current - TOTP_DRIFT..=current + TOTP_DRIFT)
+        .filter(|step| *step >= 0)
+        .find(|step| {
+            let expected = format!(
+                "{:0width$}",
+                hotp(key, *step as u64),
+                width = TOTP_DIGITS as usize
+            );
+            constant_time_eq(expected.as_bytes(), code.as_bytes())
+        })
+}
+
+/// Recovery codes are compared without their dashes, spaces and case
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+impl Customers {
+    /// Start the enrollment of a TOTP authenticator
+    /// - conn: the Database connection
+    /// - ccid: the user's UUID
+    /// - issuer: the name displayed by the authenticator (eg: Sproot)
+    ///
+    /// The secret stays pending until a code is confirmed
+    /// (see confirm_totp_enrollment). Starting again replaces it.
+    pub fn start_totp_enrollment(
+        conn: &mut ConnType,
+        ccid: &Uuid,
+        issuer: &str,
+    ) -> Result<TotpEnrollment, ApiError> {
+        let customer = Self::get_by_id(conn, ccid)?;
+        if customer.totp_enabled_at.is_some() {
+            return Err(ApiError::InvalidRequestError(Some(String::from(
+                "totp: the second factor is already enabled",
+            ))));
+        }
+
+        // 2 UUID v4 hold 244 random bits, more than the 160 recommended
+        let mut secret = Vec::with_capacity(32);
+        secret.extend_from_slice(Uuid::new_v4().as_bytes());
+        secret.extend_from_slice(Uuid::new_v4().as_bytes());
+
+        update(dsl_customers.find(ccid))
+            .set((
+                totp_secret.eq(hex::encode(&secret)),
+                totp_last_step.eq(None::<i64>),
+                totp_failures.eq(0),
+            ))
+            .execute(conn)?;
+
+        let encoded = base32_encode(&secret);
+        let uri = format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            percent_encode(issuer),
+            percent_encode(&customer.email),
+            encoded,
+            percent_encode(issuer),
+            TOTP_DIGITS,
+            TOTP_PERIOD
+        );
+
+        Ok(TotpEnrollment {
+            secret: encoded,
+            uri,
+        })
+    }
+
+    /// Enable the second factor, once the customer proved its authenticator works
+    /// - conn: the Database connection
+    /// - ccid: the user's UUID
+    /// - code: the current code of the authenticator
+    ///
+    /// Return the recovery codes, the only time their plaintext is known.
+    pub fn confirm_totp_enrollment(
+        conn: &mut ConnType,
+        ccid: &Uuid,
+        code: &str,
+    ) -> Result<Vec<String>, ApiError> {
+        let customer = Self::get_by_id(conn, ccid)?;
+        if customer.totp_enabled_at.is_some() || customer.totp_secret.is_none() {
+            return Err(ApiError::InvalidRequestError(Some(String::from(
+                "totp: no authenticator is being enrolled",
+            ))));
+        }
+        if !customer.check_totp(conn, code)? {
+            return Err(ApiError::AuthorizationError(Some(String::from(
+                "the code of the authenticator is invalid",
+            ))));
+        }
+
+        conn.transaction(|conn| {
+            update(dsl_customers.find(ccid))
+                .set(totp_enabled_at.eq(chrono::Utc::now().naive_utc()))
+                .execute(conn)?;
+
+            RecoveryCodes::regenerate(conn, ccid)
+        })
+    }
+
+    /// Disable the second factor, deleting the secret and the recovery codes
+    /// - conn: the Database connection
+    /// - ccid: the user's UUID
+    ///
+    /// The services should ask for a code (see verify_second_factor) first.
+    pub fn disable_totp(conn: &mut ConnType, ccid: &Uuid) -> Result<usize, ApiError> {
+        conn.transaction(|conn| {
+            delete(dsl_recovery.filter(cid.eq(ccid))).execute(conn)?;
+
+            Ok(update(dsl_customers.find(ccid))
+                .set((
+                    totp_secret.eq(None::<String>),
+                    totp_enabled_at.eq(None::<chrono::NaiveDateTime>),
+                    totp_last_step.eq(None::<i64>),
+                    totp_failures.eq(0),
+                    totp_locked_until.eq(None::<chrono::NaiveDateTime>),
+                ))
+                .execute(conn)?)
+        })
+    }
+
+    /// Check the second factor of the customer
+    /// - conn: the Database connection
+    /// - ccid: the user's UUID
+    /// - code: the code of the authenticator, or one of the recovery codes
+    ///
+    /// After MAX_FAILURES failed attempts in a row, every attempt is
+    /// refused for LOCK_DURATION seconds.
+    pub fn verify_second_factor(
+        conn: &mut ConnType,
+        ccid: &Uuid,
+        code: &str,
+    ) -> Result<bool, ApiError> {
+        let customer = Self::get_by_id(conn, ccid)?;
+        if customer.totp_enabled_at.is_none() {
+            return Err(ApiError::InvalidRequestError(Some(String::from(
+                "totp: the second factor is not enabled",
+            ))));
+        }
+
+        if code.len() == TOTP_DIGITS as usize {
+            return customer.check_totp(conn, code);
+        }
+
+        customer.assert_not_locked()?;
+        if RecoveryCodes::consume(conn, ccid, code)? {
+            return Ok(true);
+        }
+        customer.record_failure(conn)?;
+
+        Ok(false)
+    }
+
+    /// Refuse the attempts while the second factor is locked
+    fn assert_not_locked(&self) -> Result<(), ApiError> {
+        if self
+            .totp_locked_until
+            .is_some_and(|until| until > chrono::Utc::now().naive_utc())
+        {
+            return Err(ApiError::AuthorizationError(Some(String::from(
+                "too many invalid codes, retry later",
+            ))));
+        }
+
+        Ok(())
+    }
+
+    /// Check the code against the (pending or enabled) secret
+    ///
+    /// A time step is only accepted once, so an intercepted code can't be replayed.
+    fn check_totp(&self, conn: &mut ConnType, code: &str) -> Result<bool, ApiError> {
+        self.assert_not_locked()?;
+        let secret = match self.totp_secret.as_deref().map(hex::decode) {
+            Some(Ok(secret)) => secret,
+            _ => {
+                return Err(ApiError::ServerError(Some(format!(
+                    "totp: the secret of {} is missing or corrupted",
+                    self.id
+                ))))
+            }
+        };
+
+        let step = matching_step(&secret, code, chrono::Utc::now().timestamp())
+            .filter(|step| self.totp_last_step.is_none_or(|last| *step > last));
+        let step = match step {
+            Some(step) => step,
+            None => {
+                self.record_failure(conn)?;
+                return Ok(false);
+            }
+        };
+
+        // Guard against the same code being used concurrently
+        let updated = update(
+            dsl_customers.filter(
+                id.eq(self.id)
+                    .and(totp_last_step.is_null().or(totp_last_step.lt(step))),
+            ),
+        )
+        .set((
+            totp_last_step.eq(step),
+            totp_failures.eq(0),
+            totp_locked_until.eq(None::<chrono::NaiveDateTime>),
+        ))
+        .execute(conn)?;
+
+        Ok(updated == 1)
+    }
+
+    /// Count a failed attempt, locking the second factor after MAX_FAILURES
+    ///
+    /// Done in a single statement, so concurrent failures can't skip the lock.
+    fn record_failure(&self, conn: &mut ConnType) -> Result<(), ApiError> {
+        let reached = format!("CASE WHEN totp_failures + 1 >= {} THEN ", MAX_FAILURES);
+        let locked_until =
+            chrono::Utc::now().naive_utc() + chrono::Duration::seconds(LOCK_DURATION);
+
+        update(dsl_customers.find(self.id))
+            .set((
+                totp_failures
+                    .eq(dsl::sql::<sql_types::Integer>(&reached)
+                        .sql("0 ELSE totp_failures + 1 END")),
+                totp_locked_until.eq(dsl::sql::<sql_types::Nullable<sql_types::Timestamp>>(
+                    &reached,
+                )
+                .bind::<sql_types::Timestamp, _>(locked_until)
+                .sql(" ELSE totp_locked_until END")),
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
+
+impl RecoveryCodes {
+    /// Replace the recovery codes of the customer by new ones
+    /// - conn: the Database connection
+    /// - ccid: the user's UUID
+    ///
+    /// Return the codes (xxxxx-xxxxx), the only time their plaintext is known.
+    pub fn regenerate(conn: &mut ConnType, ccid: &Uuid) -> Result<Vec<String>, ApiError> {
+        let codes: Vec<String> = (0..RECOVERY_CODES_COUNT)
+            .map(|_| {
+                // The first 12 hex characters of a UUID v4 are random
+                let random = Uuid::new_v4().simple().to_string();
+                format!("{}-{}", &random[..5], &random[5..10])
+            })
+            .collect();
+        let values: Vec<_> = codes
+            .iter()
+            .map(|code| {
+                (
+                    cid.eq(*ccid),
+                    code_hash.eq(hash_secret(&normalize_recovery_code(code))),
+                )
+            })
+            .collect();
+
+        conn.transaction(|conn| {
+            delete(dsl_recovery.filter(cid.eq(ccid))).execute(conn)?;
+            insert_into(dsl_recovery).values(&values).execute(conn)?;
+
+            Ok(codes)
+        })
+    }
+
+    /// Use one of the recovery codes of the customer
+    /// - conn: the Database connection
+    /// - ccid: the user's UUID
+    /// - code: the recovery code given by the customer
+    ///
+    /// Return false if the code is unknown or was already used.
+    pub fn consume(conn: &mut ConnType, ccid: &Uuid, code: &str) -> Result<bool, ApiError> {
+        let updated = update(
+            dsl_recovery.filter(
+                cid.eq(ccid)
+                    .and(code_hash.eq(hash_secret(&normalize_recovery_code(code))))
+                    .and(used_at.is_null()),
+            ),
+        )
+        .set(used_at.eq(chrono::Utc::now().naive_utc()))
+        .execute(conn)?;
+
+        Ok(updated == 1)
+    }
+
+    /// Number of recovery codes the customer can still use
+    /// - conn: the Database connection
+    /// - ccid: the user's UUID
+    pub fn count_remaining(conn: &mut ConnType, ccid: &Uuid) -> Result<i64, ApiError> {
+        Ok(dsl_recovery
+            .filter(cid.eq(ccid).and(used_at.is_null()))
+            .count()
+            .get_result(conn)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Secret of the test vectors of RFC 4226 and RFC 6238 (SHA1)
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn hotp_rfc4226_vectors() {
+        let expected = [
+            755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489,
+        ];
+
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp(SECRET, counter as u64), *code, "counter {}", counter);
+        }
+    }
+
+    #[test]
+    fn totp_rfc6238_vectors() {
+        // The RFC codes have 8 digits, the last TOTP_DIGITS ones are ours
+        let expected = [
+            (59, "94287082"),
+            (1111111109, "07081804"),
+            (1111111111, "14050471"),
+            (1234567890, "89005924"),
+            (2000000000, "69279037"),
+            (20000000000, "65353130"),
+        ];
+
+        for (time, code) in expected {
+            let code = &code[code.len() - TOTP_DIGITS as usize..];
+            assert_eq!(
+                format!("{:06}", hotp(SECRET, (time / TOTP_PERIOD) as u64)),
+                code,
+                "time {}",
+                time
+            );
+            assert_eq!(matching_step(SECRET, code, time), Some(time / TOTP_PERIOD));
+        }
+    }
+
+    #[test]
+    fn matching_step_refuses_malformed_codes() {
+        assert_eq!(matching_step(SECRET, "28708", 59), None);
+        assert_eq!(matching_step(SECRET, "28708a", 59), None);
+        assert_eq!(matching_step(SECRET, "287082", 59 + 3 * TOTP_PERIOD), None);
+    }
+
+    #[test]
+    fn base32_rfc4648_vectors() {
+        // Same as the RFC, without the padding
+        let expected = [
+            ("", ""),
+            ("f", "MY"),
+            ("fo", "MZXQ"),
+            ("foo", "MZXW6"),
+            ("foob", "MZXW6YQ"),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI"),
+        ];
+
+        for (input, output) in expected {
+            assert_eq!(base32_encode(input.as_bytes()), output);
+        }
+    }
+
+    #[test]
+    fn percent_encode_reserved_characters() {
+        assert_eq!(percent_encode("a-b.c_d~e"), "a-b.c_d~e");
+        assert_eq!(percent_encode("Sproot:me@x.io"), "Sproot%3Ame%40x.io");
+        assert_eq!(percent_encode("a b/é"), "a%20b%2F%C3%A9");
+    }
+}
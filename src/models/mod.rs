@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use ts_rs::TS;
 use uuid::Uuid;
 
 use crate::{apierrors::ApiError, ConnType, Pool};
@@ -33,6 +34,16 @@ pub struct InnerUser {
     pub uuid: Uuid,
 }
 
+/// Direction in which a listing is sorted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, TS)]
+#[serde(rename_all = "lowercase")]
+#[ts(export)]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
 /// granularity == the range in which we'll group the data
 /// We'll compute the granularity from this equation:
 /// f(x) = ((0.00192859 * x) * (1.00694) + 0.298206);
@@ -58,6 +69,15 @@ pub fn get_aggregated_views<'a>(size: i64) -> &'a str {
     }
 }
 
+/// Loosely check that the value looks like an email address
+#[inline]
+pub(crate) fn is_valid_email(value: &str) -> bool {
+    match value.split_once('@') {
+        Some((local, domain)) => !local.is_empty() && domain.contains('.'),
+        None => false,
+    }
+}
+
 pub trait BaseCrud<'a> {
     type RetType;
     type VecRetType;
@@ -146,6 +146,9 @@ table! {
         cid -> Uuid,
         hostname -> Varchar,
         where_clause -> Nullable<Text>,
+        flap_window -> Nullable<Int4>,
+        flap_threshold -> Nullable<Int4>,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -162,10 +165,130 @@ table! {
         severity -> Int4,
         alerts_id -> Int8,
         cid -> Uuid,
+        acknowledged_at -> Nullable<Timestamp>,
+        flapping -> Bool,
+        flap_changes -> Int4,
+        flap_changed_at -> Nullable<Timestamp>,
+        dimension -> Varchar,
+        escalation_step -> Int4,
+    }
+}
+
+table! {
+    incidents_archive (id) {
+        id -> Int4,
+        result -> Text,
+        started_at -> Timestamp,
+        updated_at -> Timestamp,
+        resolved_at -> Nullable<Timestamp>,
+        host_uuid -> Varchar,
+        hostname -> Varchar,
+        status -> Int4,
+        severity -> Int4,
+        alerts_id -> Int8,
+        cid -> Uuid,
+        acknowledged_at -> Nullable<Timestamp>,
+        flapping -> Bool,
+        flap_changes -> Int4,
+        flap_changed_at -> Nullable<Timestamp>,
+        dimension -> Varchar,
+        archived_at -> Timestamp,
+        escalation_step -> Int4,
+    }
+}
+
+table! {
+    incidents_retention (cid) {
+        cid -> Uuid,
+        days -> Int4,
+        archive -> Bool,
+    }
+}
+
+table! {
+    notification_channels (id) {
+        id -> Int8,
+        cid -> Uuid,
+        _name -> Varchar,
+        config -> Jsonb,
+        active -> Bool,
+        min_severity -> Int4,
+        events -> Array<Text>,
+        host_uuid -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    notification_deliveries (id) {
+        id -> Int8,
+        channel_id -> Int8,
+        incident_id -> Int4,
+        cid -> Uuid,
+        event -> Varchar,
+        status -> Int4,
+        attempts -> Int4,
+        response_code -> Nullable<Int4>,
+        last_error -> Nullable<Text>,
+        next_retry_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    escalation_policies (id) {
+        id -> Int8,
+        cid -> Uuid,
+        _name -> Varchar,
+        steps -> Jsonb,
+        min_severity -> Int4,
+        alerts_id -> Nullable<Int8>,
+        host_uuid -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    oncall_schedules (id) {
+        id -> Int8,
+        cid -> Uuid,
+        _name -> Varchar,
+        timezone -> Varchar,
+        rotation -> Int4,
+        handoff_time -> Time,
+        start_date -> Date,
+        members -> Array<Uuid>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    oncall_overrides (id) {
+        id -> Int8,
+        schedule_id -> Int8,
+        user_id -> Uuid,
+        starts_at -> Timestamp,
+        ends_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    digest_subscriptions (id) {
+        id -> Int8,
+        cid -> Uuid,
+        period -> Int4,
+        recipients -> Array<Text>,
+        min_severity -> Int4,
+        active -> Bool,
+        last_sent_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
     }
 }
 
 allow_tables_to_appear_in_same_query!(incidents, alerts);
+allow_tables_to_appear_in_same_query!(incidents_archive, alerts);
 
 // !bALERTS models
 // bAUTH models
@@ -173,10 +296,33 @@ allow_tables_to_appear_in_same_query!(incidents, alerts);
 table! {
     apikeys (id) {
         id -> Int8,
-        key -> Text,
         host_uuid -> Nullable<Text>,
         customer_id -> Uuid,
         berta -> Text,
+        prefix -> Text,
+        key_hash -> Text,
+        scopes -> Array<Text>,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+        enrolled -> Bool,
+    }
+}
+
+table! {
+    enrollment_tokens (id) {
+        id -> Int8,
+        cid -> Uuid,
+        _name -> Varchar,
+        prefix -> Text,
+        token_hash -> Text,
+        berta -> Text,
+        max_uses -> Int4,
+        uses -> Int4,
+        expires_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
     }
 }
 
@@ -184,9 +330,88 @@ table! {
     customers (id) {
         id -> Uuid,
         email -> Varchar,
+        display_name -> Nullable<Varchar>,
+        timezone -> Varchar,
+        notification_prefs -> Jsonb,
+        pending_email -> Nullable<Varchar>,
+        email_token_hash -> Nullable<Text>,
+        email_token_expires_at -> Nullable<Timestamp>,
+        totp_secret -> Nullable<Text>,
+        totp_enabled_at -> Nullable<Timestamp>,
+        totp_last_step -> Nullable<Int8>,
+        totp_failures -> Int4,
+        totp_locked_until -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    recovery_codes (id) {
+        id -> Int8,
+        cid -> Uuid,
+        code_hash -> Text,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
     }
 }
 
-allow_tables_to_appear_in_same_query!(apikeys, customers);
+table! {
+    organizations (id) {
+        id -> Uuid,
+        _name -> Varchar,
+        personal -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    organization_members (org_id, customer_id) {
+        org_id -> Uuid,
+        customer_id -> Uuid,
+        created_at -> Timestamp,
+        role -> Varchar,
+    }
+}
+
+table! {
+    organization_invitations (id) {
+        id -> Int8,
+        org_id -> Uuid,
+        email -> Varchar,
+        token_hash -> Text,
+        invited_by -> Uuid,
+        expires_at -> Timestamp,
+        accepted_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        role -> Varchar,
+    }
+}
+
+table! {
+    login_tokens (id) {
+        id -> Int8,
+        cid -> Uuid,
+        token_hash -> Text,
+        ip -> Text,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+joinable!(organization_members -> organizations (org_id));
+joinable!(organization_members -> customers (customer_id));
+joinable!(organization_invitations -> organizations (org_id));
+joinable!(login_tokens -> customers (cid));
+joinable!(recovery_codes -> customers (cid));
+
+allow_tables_to_appear_in_same_query!(
+    apikeys,
+    customers,
+    organizations,
+    organization_members,
+    organization_invitations,
+    login_tokens,
+    recovery_codes
+);
 
 // !bAUTH models
@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+
+use chrono::SecondsFormat;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{dedup_key, IncidentEvent, IncidentMessage, Notifier, WebhookSender};
+use crate::apierrors::ApiError;
+use crate::models::{ChannelConfig, IncidentsJoined, NotificationChannels};
+
+/// Value of endsAt for the alerts still firing (Go's zero time)
+const ZERO_TIME: &str = "0001-01-01T00:00:00Z";
+
+/// Body of the Prometheus Alertmanager webhooks (version 4)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertmanagerPayload {
+    pub version: String,
+    pub group_key: String,
+    pub truncated_alerts: u64,
+    // firing or resolved
+    pub status: String,
+    pub receiver: String,
+    pub group_labels: BTreeMap<String, String>,
+    pub common_labels: BTreeMap<String, String>,
+    pub common_annotations: BTreeMap<String, String>,
+    #[serde(rename = "externalURL")]
+    pub external_url: String,
+    pub alerts: Vec<AlertmanagerAlert>,
+}
+
+/// An alert inside an AlertmanagerPayload
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertmanagerAlert {
+    pub status: String,
+    pub labels: BTreeMap<String, String>,
+    pub annotations: BTreeMap<String, String>,
+    pub starts_at: String,
+    pub ends_at: String,
+    #[serde(rename = "generatorURL")]
+    pub generator_url: String,
+    pub fingerprint: String,
+}
+
+impl AlertmanagerPayload {
+    /// Render the event of the incident as an Alertmanager webhook
+    /// - incident: the incident the event is about
+    /// - event: the event to send
+    /// - base_url: the url of the dashboard, used to build the links
+    ///
+    /// Every payload holds a single alert, grouped by the dedup_key.
+    pub fn new(incident: &IncidentsJoined, event: IncidentEvent, base_url: &str) -> Self {
+        let inner = &incident.incident;
+        let message = IncidentMessage::new(incident, event, base_url);
+        let key = dedup_key(inner);
+        let status = match event {
+            IncidentEvent::Resolved => "resolved",
+            _ => "firing",
+        };
+
+        let mut labels = BTreeMap::from([
+            (String::from("alertname"), message.alert_name.clone()),
+            (String::from("alert_id"), inner.alerts_id.to_string()),
+            (String::from("host_uuid"), inner.host_uuid.clone()),
+            (String::from("hostname"), inner.hostname.clone()),
+            (String::from("severity"), message.severity.to_owned()),
+        ]);
+        if !inner.dimension.is_empty() {
+            labels.insert(String::from("dimension"), inner.dimension.clone());
+        }
+
+        let mut annotations = BTreeMap::from([
+            (String::from("summary"), message.summary.clone()),
+            (String::from("value"), inner.result.clone()),
+        ]);
+        if let Some(info) = incident.alert.as_ref().and_then(|a| a.info.clone()) {
+            annotations.insert(String::from("description"), info);
+        }
+
+        let alert = AlertmanagerAlert {
+            status: status.to_owned(),
+            labels: labels.clone(),
+            annotations: annotations.clone(),
+            starts_at: inner
+                .started_at
+                .and_utc()
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            ends_at: inner.resolved_at.map_or_else(
+                || ZERO_TIME.to_owned(),
+                |at| at.and_utc().to_rfc3339_opts(SecondsFormat::Secs, true),
+            ),
+            generator_url: message.incident_url.clone(),
+            // Alertmanager use a 16 hex chars hash of the labels
+            fingerprint: hex::encode(&Sha256::digest(key.as_bytes())[..8]),
+        };
+
+        Self {
+            version: String::from("4"),
+            group_key: format!("{{}}:{{dedup_key=\"{}\"}}", key),
+            truncated_alerts: 0,
+            status: status.to_owned(),
+            receiver: String::from("sproot"),
+            group_labels: BTreeMap::from([(String::from("dedup_key"), key)]),
+            common_labels: labels,
+            common_annotations: annotations,
+            external_url: base_url.trim_end_matches('/').to_owned(),
+            alerts: vec![alert],
+        }
+    }
+}
+
+/// Send the incidents events to an Alertmanager webhook receiver
+pub struct AlertmanagerNotifier {
+    sender: WebhookSender,
+    url: String,
+    base_url: String,
+}
+
+impl AlertmanagerNotifier {
+    pub fn new(sender: WebhookSender, url: &str, base_url: &str) -> Self {
+        Self {
+            sender,
+            url: url.to_owned(),
+            base_url: base_url.to_owned(),
+        }
+    }
+
+    /// Create the notifier of an Alertmanager channel
+    /// - channel: the alertmanager channel
+    /// - sender: the sender used to POST the payloads
+    /// - base_url: the url of the dashboard, used to build the links
+    pub fn from_channel(
+        channel: &NotificationChannels,
+        sender: WebhookSender,
+        base_url: &str,
+    ) -> Result<Self, ApiError> {
+        match &channel.config {
+            ChannelConfig::Alertmanager { url } => Ok(Self::new(sender, url, base_url)),
+            _ => Err(ApiError::InvalidRequestError(Some(format!(
+                "alertmanager: channel {} is not an alertmanager channel",
+                channel.id
+            )))),
+        }
+    }
+}
+
+impl Notifier for AlertmanagerNotifier {
+    fn send(&self, incident: &IncidentsJoined, event: IncidentEvent) -> Result<(), ApiError> {
+        let body = serde_json::to_vec(&AlertmanagerPayload::new(incident, event, &self.base_url))?;
+
+        self.sender.post(&self.url, &body, None, &[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::testing::{incident, resolved};
+
+    const BASE_URL: &str = "https://sproot.local/";
+
+    #[test]
+    fn firing_payload() {
+        let payload = AlertmanagerPayload::new(
+            &incident("cpu usage", "sda1"),
+            IncidentEvent::Opened,
+            BASE_URL,
+        );
+        let alert = &payload.alerts[0];
+
+        assert_eq!(payload.version, "4");
+        assert_eq!(payload.status, "firing");
+        assert_eq!(payload.external_url, "https://sproot.local");
+        assert_eq!(
+            payload.group_key,
+            "{}:{dedup_key=\"sproot-7-host-uuid-sda1\"}"
+        );
+        assert_eq!(payload.group_labels["dedup_key"], "sproot-7-host-uuid-sda1");
+        assert_eq!(payload.alerts.len(), 1);
+        assert_eq!(alert.status, "firing");
+        assert_eq!(alert.labels["alertname"], "cpu usage");
+        assert_eq!(alert.labels["severity"], "critical");
+        assert_eq!(alert.labels["dimension"], "sda1");
+        assert_eq!(alert.annotations["value"], "92");
+        assert_eq!(alert.annotations["description"], "The cpu is busy");
+        assert_eq!(alert.starts_at, "2023-11-14T22:13:20Z");
+        assert_eq!(alert.ends_at, ZERO_TIME);
+        assert_eq!(alert.generator_url, "https://sproot.local/incidents/1");
+        assert_eq!(alert.fingerprint.len(), 16);
+    }
+
+    #[test]
+    fn resolved_payload() {
+        let firing =
+            AlertmanagerPayload::new(&incident("cpu usage", ""), IncidentEvent::Opened, BASE_URL);
+        let payload = AlertmanagerPayload::new(
+            &resolved(incident("cpu usage", "")),
+            IncidentEvent::Resolved,
+            BASE_URL,
+        );
+        let alert = &payload.alerts[0];
+
+        assert_eq!(payload.status, "resolved");
+        assert_eq!(alert.status, "resolved");
+        assert_eq!(alert.ends_at, "2023-11-14T22:23:20Z");
+        assert!(!alert.labels.contains_key("dimension"));
+        // Grouped with the firing alert by Alertmanager
+        assert_eq!(payload.group_key, firing.group_key);
+        assert_eq!(alert.fingerprint, firing.alerts[0].fingerprint);
+    }
+
+    #[test]
+    fn serialized_field_names() {
+        let payload =
+            AlertmanagerPayload::new(&incident("cpu usage", ""), IncidentEvent::Opened, BASE_URL);
+        let value = serde_json::to_value(&payload).unwrap();
+
+        for field in [
+            "version",
+            "groupKey",
+            "truncatedAlerts",
+            "status",
+            "receiver",
+            "groupLabels",
+            "commonLabels",
+            "commonAnnotations",
+            "externalURL",
+            "alerts",
+        ] {
+            assert!(value.get(field).is_some(), "missing {}", field);
+        }
+        for field in [
+            "status",
+            "labels",
+            "annotations",
+            "startsAt",
+            "endsAt",
+            "generatorURL",
+            "fingerprint",
+        ] {
+            assert!(value["alerts"][0].get(field).is_some(), "missing {}", field);
+        }
+    }
+}
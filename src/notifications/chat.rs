@@ -0,0 +1,414 @@
+use serde_json::{json, Value};
+
+use super::{IncidentEvent, IncidentMessage, Notifier, WebhookSender};
+use crate::apierrors::ApiError;
+use crate::models::{ChannelConfig, IncidentSeverity, IncidentsJoined, NotificationChannels};
+
+/// Chat app receiving the incidents events through its incoming webhooks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatPlatform {
+    Slack,
+    Discord,
+    Teams,
+    Mattermost,
+}
+
+impl ChatPlatform {
+    /// Get the platform and the webhook url of a chat channel
+    pub fn from_config(config: &ChannelConfig) -> Option<(Self, &str)> {
+        match config {
+            ChannelConfig::Slack { url } => Some((ChatPlatform::Slack, url)),
+            ChannelConfig::Discord { url } => Some((ChatPlatform::Discord, url)),
+            ChannelConfig::Teams { url } => Some((ChatPlatform::Teams, url)),
+            ChannelConfig::Mattermost { url } => Some((ChatPlatform::Mattermost, url)),
+            _ => None,
+        }
+    }
+
+    /// Turn the message into the native format of the platform
+    pub fn format(&self, message: &IncidentMessage) -> Value {
+        match self {
+            ChatPlatform::Slack => slack_blocks(message),
+            ChatPlatform::Discord => discord_embed(message),
+            ChatPlatform::Teams => teams_card(message),
+            ChatPlatform::Mattermost => mattermost_attachment(message),
+        }
+    }
+}
+
+/// Facts displayed as fields by every platform
+fn facts(message: &IncidentMessage) -> Vec<(&'static str, String)> {
+    let mut facts = vec![
+        ("Severity", message.severity.to_owned()),
+        ("Host", message.hostname.clone()),
+    ];
+    if !message.dimension.is_empty() {
+        facts.push(("Target", message.dimension.clone()));
+    }
+    facts.push(("Value", message.value.clone()));
+    facts.push(("Duration", message.duration.clone()));
+
+    facts
+}
+
+/// Escape the control characters of the Slack mrkdwn (&, < and >)
+///
+/// Slack does not support escaping the formatting characters (*, _, ~).
+fn slack_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Escape the markdown formatting characters (Discord, Teams and Mattermost)
+fn markdown_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '#' | '[' | ']' | '(' | ')'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Slack: blocks inside an attachment (the only way to get a colored bar)
+fn slack_blocks(message: &IncidentMessage) -> Value {
+    let fields: Vec<Value> = facts(message)
+        .into_iter()
+        .map(|(name, value)| {
+            json!({"type": "mrkdwn", "text": format!("*{}*\n{}", name, slack_escape(&value))})
+        })
+        .collect();
+
+    json!({
+        "text": slack_escape(&message.summary),
+        "attachments": [{
+            "color": message.color,
+            "blocks": [
+                {
+                    "type": "header",
+                    "text": {"type": "plain_text", "text": message.title},
+                },
+                {
+                    "type": "section",
+                    "text": {
+                        "type": "mrkdwn",
+                        "text": format!(
+                            "*{}* on *{}*",
+                            slack_escape(&message.alert_name),
+                            slack_escape(&message.target)
+                        ),
+                    },
+                    "fields": fields,
+                },
+                {
+                    "type": "actions",
+                    "elements": [{
+                        "type": "button",
+                        "text": {"type": "plain_text", "text": "View the incident"},
+                        "url": message.incident_url,
+                    }],
+                },
+            ],
+        }],
+    })
+}
+
+/// Discord: a single embed, whose color is an integer
+fn discord_embed(message: &IncidentMessage) -> Value {
+    let color = u32::from_str_radix(message.color.trim_start_matches('#'), 16).unwrap_or(0);
+    let fields: Vec<Value> = facts(message)
+        .into_iter()
+        .map(
+            |(name, value)| json!({"name": name, "value": markdown_escape(&value), "inline": true}),
+        )
+        .collect();
+
+    json!({
+        "embeds": [{
+            "title": message.title,
+            "description": format!(
+                "**{}** on **{}**",
+                markdown_escape(&message.alert_name),
+                markdown_escape(&message.target)
+            ),
+            "url": message.incident_url,
+            "color": color,
+            "fields": fields,
+            "footer": {"text": message.summary},
+        }],
+    })
+}
+
+/// Teams: an adaptive card, which only supports named colors
+fn teams_card(message: &IncidentMessage) -> Value {
+    let color = match (message.event, message.severity) {
+        (IncidentEvent::Resolved, _) => "Good",
+        (_, severity) if severity == IncidentSeverity::Warning.as_str() => "Warning",
+        _ => "Attention",
+    };
+    let facts: Vec<Value> = facts(message)
+        .into_iter()
+        .map(|(name, value)| json!({"title": name, "value": markdown_escape(&value)}))
+        .collect();
+
+    json!({
+        "type": "message",
+        "summary": message.summary,
+        "attachments": [{
+            "contentType": "application/vnd.microsoft.card.adaptive",
+            "content": {
+                "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                "type": "AdaptiveCard",
+                "version": "1.4",
+                "body": [
+                    {
+                        "type": "TextBlock",
+                        "text": message.title,
+                        "weight": "Bolder",
+                        "size": "Medium",
+                        "color": color,
+                    },
+                    {
+                        "type": "TextBlock",
+                        "text": format!(
+                            "**{}** on **{}**",
+                            markdown_escape(&message.alert_name),
+                            markdown_escape(&message.target)
+                        ),
+                        "wrap": true,
+                    },
+                    {"type": "FactSet", "facts": facts},
+                ],
+                "actions": [{
+                    "type": "Action.OpenUrl",
+                    "title": "View the incident",
+                    "url": message.incident_url,
+                }],
+            },
+        }],
+    })
+}
+
+/// Mattermost: a Slack-like (legacy) attachment
+fn mattermost_attachment(message: &IncidentMessage) -> Value {
+    let fields: Vec<Value> = facts(message)
+        .into_iter()
+        .map(
+            |(name, value)| json!({"short": true, "title": name, "value": markdown_escape(&value)}),
+        )
+        .collect();
+
+    json!({
+        "attachments": [{
+            "fallback": message.summary,
+            "color": message.color,
+            "title": message.title,
+            "title_link": message.incident_url,
+            "text": format!(
+                "**{}** on **{}**",
+                markdown_escape(&message.alert_name),
+                markdown_escape(&message.target)
+            ),
+            "fields": fields,
+        }],
+    })
+}
+
+/// Send the incidents events to a chat app, in its native format
+pub struct ChatNotifier {
+    sender: WebhookSender,
+    platform: ChatPlatform,
+    url: String,
+    base_url: String,
+}
+
+impl ChatNotifier {
+    /// Create a notifier posting to the incoming webhook of the platform
+    /// - sender: the sender used to POST the messages
+    /// - platform: the chat app behind the url
+    /// - url: the incoming webhook url
+    /// - base_url: the url of the dashboard, used to build the links
+    pub fn new(sender: WebhookSender, platform: ChatPlatform, url: &str, base_url: &str) -> Self {
+        Self {
+            sender,
+            platform,
+            url: url.to_owned(),
+            base_url: base_url.to_owned(),
+        }
+    }
+
+    /// Create the notifier of a chat channel
+    /// - channel: the chat channel (slack, discord, teams or mattermost)
+    /// - sender: the sender used to POST the messages
+    /// - base_url: the url of the dashboard, used to build the links
+    pub fn from_channel(
+        channel: &NotificationChannels,
+        sender: WebhookSender,
+        base_url: &str,
+    ) -> Result<Self, ApiError> {
+        match ChatPlatform::from_config(&channel.config) {
+            Some((platform, url)) => Ok(Self::new(sender, platform, url, base_url)),
+            None => Err(ApiError::InvalidRequestError(Some(format!(
+                "chat: channel {} is not a chat channel",
+                channel.id
+            )))),
+        }
+    }
+}
+
+impl Notifier for ChatNotifier {
+    fn send(&self, incident: &IncidentsJoined, event: IncidentEvent) -> Result<(), ApiError> {
+        let message = IncidentMessage::new(incident, event, &self.base_url);
+        let body = serde_json::to_vec(&self.platform.format(&message))?;
+
+        self.sender.post(&self.url, &body, None, &[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::notifications::testing::incident;
+    use crate::notifications::RetryPolicy;
+
+    /// Send the incident to a local listener standing for the platform, return the JSON received
+    fn post(platform: ChatPlatform) -> Value {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            tx.send(body).unwrap();
+        });
+
+        let sender = WebhookSender::new(Duration::from_secs(5), RetryPolicy::default());
+        ChatNotifier::new(sender, platform, &url, "https://sproot.local/")
+            .send(
+                &incident("<!channel> *cpu* & [link](x)", ""),
+                IncidentEvent::Opened,
+            )
+            .unwrap();
+
+        serde_json::from_slice(&rx.recv_timeout(Duration::from_secs(5)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn slack_escapes_the_control_characters() {
+        assert_eq!(slack_escape("<!here> a&b"), "&lt;!here&gt; a&amp;b");
+    }
+
+    #[test]
+    fn markdown_escapes_the_formatting_characters() {
+        assert_eq!(
+            markdown_escape("*a_b* [c](d)"),
+            "\\*a\\_b\\* \\[c\\]\\(d\\)"
+        );
+    }
+
+    #[test]
+    fn slack_payload() {
+        let body = post(ChatPlatform::Slack);
+        let attachment = &body["attachments"][0];
+
+        assert_eq!(attachment["color"], "#cf222e");
+        assert_eq!(attachment["blocks"][0]["type"], "header");
+        assert_eq!(attachment["blocks"][0]["text"]["text"], "Incident opened");
+        assert_eq!(
+            attachment["blocks"][1]["text"]["text"],
+            "*&lt;!channel&gt; *cpu* &amp; [link](x)* on *web_01*"
+        );
+        assert_eq!(attachment["blocks"][1]["fields"][0]["type"], "mrkdwn");
+        assert_eq!(
+            attachment["blocks"][2]["elements"][0]["url"],
+            "https://sproot.local/incidents/1"
+        );
+        assert!(!body["text"].as_str().unwrap().contains('<'));
+    }
+
+    #[test]
+    fn discord_payload() {
+        let body = post(ChatPlatform::Discord);
+        let embed = &body["embeds"][0];
+
+        assert_eq!(embed["title"], "Incident opened");
+        assert_eq!(embed["color"], 0xcf222e);
+        assert_eq!(
+            embed["description"],
+            "**<!channel\\> \\*cpu\\* & \\[link\\]\\(x\\)** on **web\\_01**"
+        );
+        assert_eq!(embed["fields"][0]["name"], "Severity");
+        assert_eq!(embed["fields"][0]["inline"], true);
+        assert_eq!(embed["url"], "https://sproot.local/incidents/1");
+    }
+
+    #[test]
+    fn teams_payload() {
+        let body = post(ChatPlatform::Teams);
+        let content = &body["attachments"][0]["content"];
+
+        assert_eq!(body["type"], "message");
+        assert_eq!(
+            body["attachments"][0]["contentType"],
+            "application/vnd.microsoft.card.adaptive"
+        );
+        assert_eq!(content["type"], "AdaptiveCard");
+        assert_eq!(content["body"][0]["color"], "Attention");
+        assert_eq!(content["body"][2]["type"], "FactSet");
+        assert_eq!(content["body"][2]["facts"][1]["value"], "web\\_01");
+        assert_eq!(
+            content["actions"][0]["url"],
+            "https://sproot.local/incidents/1"
+        );
+    }
+
+    #[test]
+    fn mattermost_payload() {
+        let body = post(ChatPlatform::Mattermost);
+        let attachment = &body["attachments"][0];
+
+        assert_eq!(attachment["color"], "#cf222e");
+        assert_eq!(attachment["title"], "Incident opened");
+        assert_eq!(attachment["title_link"], "https://sproot.local/incidents/1");
+        assert_eq!(
+            attachment["text"],
+            "**<!channel\\> \\*cpu\\* & \\[link\\]\\(x\\)** on **web\\_01**"
+        );
+        assert_eq!(attachment["fields"][1]["title"], "Host");
+        assert_eq!(attachment["fields"][1]["short"], true);
+    }
+}
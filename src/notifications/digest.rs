@@ -0,0 +1,268 @@
+use askama::Template;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::Message;
+
+use super::{human_duration, EmailTransport};
+use crate::apierrors::ApiError;
+use crate::models::{
+    DigestPeriod, DigestSubscriptions, EmailKind, IncidentSeverity, IncidentStatus, Incidents,
+    IncidentsJoined, Organizations,
+};
+use crate::ConnType;
+
+/// Summary of the incidents of a customer over a period
+pub struct IncidentDigest {
+    pub subject: String,
+    pub period: &'static str,
+    pub from: String,
+    pub to: String,
+    pub total: usize,
+    // Incidents grouped per host and severity
+    pub groups: Vec<DigestGroup>,
+    pub dashboard_url: String,
+}
+
+/// Incidents of a single host with the same severity
+pub struct DigestGroup {
+    pub hostname: String,
+    pub severity: &'static str,
+    pub color: &'static str,
+    pub count: usize,
+    // Time spent in incident during the period (overlaps counted twice)
+    pub duration: String,
+    pub host_url: String,
+    pub incidents: Vec<DigestEntry>,
+}
+
+/// Line of a single incident in the digest
+pub struct DigestEntry {
+    pub alert_name: String,
+    pub target: String,
+    pub value: String,
+    pub started_at: String,
+    pub status: &'static str,
+    pub duration: String,
+    pub incident_url: String,
+}
+
+impl IncidentDigest {
+    /// Build the digest of the incidents
+    /// - incidents: the incidents, sorted by hostname then severity
+    /// - period: the period covered by the digest
+    /// - from: start of the period
+    /// - to: end of the period
+    /// - base_url: the url of the dashboard, used to build the links
+    ///
+    /// The durations are clipped to the period, the still active
+    /// incidents being counted up to its end.
+    pub fn new(
+        incidents: &[IncidentsJoined],
+        period: DigestPeriod,
+        from: chrono::NaiveDateTime,
+        to: chrono::NaiveDateTime,
+        base_url: &str,
+    ) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+
+        let mut groups: Vec<DigestGroup> = Vec::new();
+        let mut group_total = chrono::Duration::zero();
+        let mut group_key: Option<(&str, i32)> = None;
+        for joined in incidents {
+            let inner = &joined.incident;
+            let key = (inner.host_uuid.as_str(), inner.severity);
+            if group_key != Some(key) {
+                if let Some(group) = groups.last_mut() {
+                    group.duration = human_duration(group_total);
+                }
+                group_total = chrono::Duration::zero();
+                group_key = Some(key);
+
+                let severity = IncidentSeverity::from_i32(inner.severity);
+                groups.push(DigestGroup {
+                    hostname: inner.hostname.clone(),
+                    severity: severity.map_or("unknown", |s| s.as_str()),
+                    color: match severity {
+                        Some(IncidentSeverity::Warning) => "#bf8700",
+                        _ => "#cf222e",
+                    },
+                    count: 0,
+                    duration: String::new(),
+                    host_url: format!("{}/hosts/{}", base_url, inner.host_uuid),
+                    incidents: Vec::new(),
+                });
+            }
+
+            let duration = clipped_duration(inner, from, to);
+            group_total += duration;
+
+            if let Some(group) = groups.last_mut() {
+                group.count += 1;
+                group.incidents.push(DigestEntry {
+                    alert_name: joined
+                        .alert
+                        .as_ref()
+                        .map(|alert| alert.name.clone())
+                        .unwrap_or_else(|| format!("alert #{}", inner.alerts_id)),
+                    target: if inner.dimension.is_empty() {
+                        inner.hostname.clone()
+                    } else {
+                        format!("{}:{}", inner.hostname, inner.dimension)
+                    },
+                    value: inner.result.clone(),
+                    started_at: inner.started_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    status: if inner.status == IncidentStatus::Active as i32 {
+                        "active"
+                    } else {
+                        "resolved"
+                    },
+                    duration: human_duration(duration),
+                    incident_url: format!("{}/incidents/{}", base_url, inner.id),
+                });
+            }
+        }
+        if let Some(group) = groups.last_mut() {
+            group.duration = human_duration(group_total);
+        }
+
+        let (from, to) = (
+            from.format("%Y-%m-%d %H:%M").to_string(),
+            to.format("%Y-%m-%d %H:%M").to_string(),
+        );
+
+        Self {
+            subject: format!(
+                "[sproot] {} digest: {} incident(s) from {} to {}",
+                capitalize(period.as_str()),
+                incidents.len(),
+                from,
+                to
+            ),
+            period: period.as_str(),
+            from,
+            to,
+            total: incidents.len(),
+            groups,
+            dashboard_url: format!("{}/incidents", base_url),
+        }
+    }
+
+    /// Build the email (plain text and html alternatives) for the recipients
+    pub fn to_email(&self, from: &Mailbox, to: &[Mailbox]) -> Result<Message, ApiError> {
+        let mut builder = Message::builder().from(from.clone()).subject(&self.subject);
+        for recipient in to {
+            builder = builder.to(recipient.clone());
+        }
+
+        Ok(builder.multipart(MultiPart::alternative_plain_html(
+            DigestEmailText { digest: self }.render()?,
+            DigestEmailHtml { digest: self }.render()?,
+        ))?)
+    }
+
+    /// Send the digests which are due to their recipients
+    /// - conn: the Database connection
+    /// - transport: the way the emails are delivered
+    /// - from: the sender of the emails
+    /// - base_url: the url of the dashboard, used to build the links
+    /// - now: the current time, end of the period covered by the digests
+    ///
+    /// A subscription is marked as sent before its email goes out, so a
+    /// digest is never sent twice, even with multiple workers running.
+    /// The mark is rolled back if the email cannot be sent, so it is retried.
+    /// Return the number of digests sent.
+    pub fn send_due(
+        conn: &mut ConnType,
+        transport: &EmailTransport,
+        from: &str,
+        base_url: &str,
+        now: chrono::NaiveDateTime,
+    ) -> Result<usize, ApiError> {
+        let sender: Mailbox = from.parse()?;
+
+        let mut sent = 0;
+        for sub in DigestSubscriptions::get_due(conn, now)? {
+            if !sub.mark_sent(conn, now)? {
+                continue;
+            }
+
+            let result = Self::build_email(conn, &sub, &sender, base_url, now)
+                .and_then(|message| message.map(|message| transport.send(&message)).transpose());
+            match result {
+                Ok(Some(_)) => sent += 1,
+                // Nobody wants the digest, it is skipped for this period
+                Ok(None) => {}
+                Err(err) => {
+                    error!("digest: subscription {} failed: {}", sub.id, err);
+                    sub.unmark_sent(conn, now)?;
+                }
+            }
+        }
+
+        Ok(sent)
+    }
+
+    /// Build the digest email of the subscription for the period since the previous one
+    ///
+    /// Without explicit recipients, the digest goes to the members wanting
+    /// it (None if none of them do).
+    fn build_email(
+        conn: &mut ConnType,
+        sub: &DigestSubscriptions,
+        sender: &Mailbox,
+        base_url: &str,
+        now: chrono::NaiveDateTime,
+    ) -> Result<Option<Message>, ApiError> {
+        let period = sub.period();
+        let start = sub.period_start();
+        let incidents = Incidents::get_for_digest(conn, &sub.cid, sub.min_severity, start, now)?;
+
+        let recipients = if sub.recipients.is_empty() {
+            Organizations::get_notified_emails(conn, &sub.cid, EmailKind::Digest)?
+        } else {
+            sub.recipients.clone()
+        };
+        if recipients.is_empty() {
+            return Ok(None);
+        }
+        let recipients = recipients
+            .iter()
+            .map(|recipient| recipient.parse())
+            .collect::<Result<Vec<Mailbox>, _>>()?;
+
+        Self::new(&incidents, period, start, now, base_url)
+            .to_email(sender, &recipients)
+            .map(Some)
+    }
+}
+
+/// Part of the incident which happened during the period
+fn clipped_duration(
+    incident: &Incidents,
+    from: chrono::NaiveDateTime,
+    to: chrono::NaiveDateTime,
+) -> chrono::Duration {
+    let start = incident.started_at.max(from);
+    let end = incident.resolved_at.unwrap_or(to).min(to);
+
+    (end - start).max(chrono::Duration::zero())
+}
+
+fn capitalize(value: &str) -> String {
+    let mut chars = value.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "email/digest.txt")]
+struct DigestEmailText<'a> {
+    digest: &'a IncidentDigest,
+}
+
+#[derive(Template)]
+#[template(path = "email/digest.html")]
+struct DigestEmailHtml<'a> {
+    digest: &'a IncidentDigest,
+}
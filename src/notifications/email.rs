@@ -0,0 +1,253 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use askama::Template;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::file::FileTransport;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+
+use super::{IncidentEvent, IncidentMessage, Notifier};
+use crate::apierrors::ApiError;
+use crate::models::{
+    ChannelConfig, EmailKind, IncidentsJoined, NotificationChannels, OncallSchedules, Organizations,
+};
+use crate::ConnType;
+
+/// How the connection to the SMTP server is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    // TLS from the start of the connection (usually port 465)
+    Tls,
+    // Plain connection upgraded using STARTTLS (usually port 587)
+    StartTls,
+    // No encryption at all, only meant for local testing (eg: MailHog)
+    Plain,
+}
+
+/// Way the emails are delivered
+///
+/// File and Mbox allow to test the whole thing locally without a mail server.
+pub enum EmailTransport {
+    // Send the emails through a SMTP server
+    Smtp(SmtpTransport),
+    // Write each email as a .eml file in a directory
+    File(FileTransport),
+    // Append every emails to a single mbox file
+    Mbox { path: PathBuf, lock: Mutex<()> },
+}
+
+impl EmailTransport {
+    /// Create a transport sending the emails through a SMTP server
+    /// - host: the hostname of the SMTP server
+    /// - port: the port of the SMTP server
+    /// - security: how the connection is secured
+    /// - credentials: the username and password, if the server needs them
+    pub fn smtp(
+        host: &str,
+        port: u16,
+        security: SmtpSecurity,
+        credentials: Option<(String, String)>,
+    ) -> Result<Self, ApiError> {
+        let mut builder = match security {
+            SmtpSecurity::Tls => SmtpTransport::relay(host)?,
+            SmtpSecurity::StartTls => SmtpTransport::starttls_relay(host)?,
+            SmtpSecurity::Plain => SmtpTransport::builder_dangerous(host),
+        }
+        .port(port);
+
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(EmailTransport::Smtp(builder.build()))
+    }
+
+    /// Create a transport writing each email as a .eml file in the directory
+    pub fn file<P: Into<PathBuf>>(dir: P) -> Self {
+        EmailTransport::File(FileTransport::new(dir.into()))
+    }
+
+    /// Create a transport appending the emails to the mbox file
+    pub fn mbox<P: Into<PathBuf>>(path: P) -> Self {
+        EmailTransport::Mbox {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Deliver the message using the transport
+    pub fn send(&self, message: &Message) -> Result<(), ApiError> {
+        match self {
+            EmailTransport::Smtp(transport) => {
+                transport.send(message)?;
+            }
+            EmailTransport::File(transport) => {
+                transport.send(message)?;
+            }
+            EmailTransport::Mbox { path, lock } => {
+                let entry = mbox_entry(message);
+                // The lock only guard the file, a poisoned one is still usable
+                let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)?
+                    .write_all(entry.as_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Format the message as an entry of a mbox file (mboxrd flavor)
+fn mbox_entry(message: &Message) -> String {
+    let sender = message
+        .envelope()
+        .from()
+        .map(|addr| addr.to_string())
+        .unwrap_or_else(|| String::from("MAILER-DAEMON"));
+    let raw = String::from_utf8_lossy(&message.formatted()).replace("\r\n", "\n");
+
+    let mut entry = format!(
+        "From {} {}\n",
+        sender,
+        chrono::Utc::now().format("%a %b %e %H:%M:%S %Y")
+    );
+    for line in raw.lines() {
+        // Quote the lines which could be mistaken for the start of a new entry
+        if line.trim_start_matches('>').starts_with("From ") {
+            entry.push('>');
+        }
+        entry.push_str(line);
+        entry.push('\n');
+    }
+    entry.push('\n');
+
+    entry
+}
+
+impl IncidentMessage {
+    /// Build the email (plain text and html alternatives) for the recipients
+    pub fn to_email(&self, from: &Mailbox, to: &[Mailbox]) -> Result<Message, ApiError> {
+        let mut builder = Message::builder().from(from.clone()).subject(&self.subject);
+        for recipient in to {
+            builder = builder.to(recipient.clone());
+        }
+
+        Ok(builder.multipart(MultiPart::alternative_plain_html(
+            IncidentEmailText { email: self }.render()?,
+            IncidentEmailHtml { email: self }.render()?,
+        ))?)
+    }
+}
+
+#[derive(Template)]
+#[template(path = "email/incident.txt")]
+struct IncidentEmailText<'a> {
+    email: &'a IncidentMessage,
+}
+
+#[derive(Template)]
+#[template(path = "email/incident.html")]
+struct IncidentEmailHtml<'a> {
+    email: &'a IncidentMessage,
+}
+
+/// Send the incidents events by email
+pub struct EmailNotifier {
+    transport: Arc<EmailTransport>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    base_url: String,
+}
+
+impl EmailNotifier {
+    /// Create a notifier sending the emails to the recipients
+    /// - transport: the way the emails are delivered
+    /// - from: the sender of the emails
+    /// - to: the recipients of the emails
+    /// - base_url: the url of the dashboard, used to build the links
+    pub fn new(
+        transport: Arc<EmailTransport>,
+        from: &str,
+        to: &[String],
+        base_url: &str,
+    ) -> Result<Self, ApiError> {
+        if to.is_empty() {
+            return Err(ApiError::InvalidRequestError(Some(String::from(
+                "email: at least one recipient is needed",
+            ))));
+        }
+
+        Ok(Self {
+            transport,
+            from: from.parse()?,
+            to: to
+                .iter()
+                .map(|recipient| recipient.parse())
+                .collect::<Result<_, _>>()?,
+            base_url: base_url.to_owned(),
+        })
+    }
+
+    /// Create the notifier of an email channel
+    /// - conn: the Database connection
+    /// - channel: the email channel
+    /// - transport: the way the emails are delivered
+    /// - from: the sender of the emails
+    /// - base_url: the url of the dashboard, used to build the links
+    ///
+    /// The user currently on call of its schedule (if any) is added to the
+    /// recipients. Without any recipient, the emails go to the members of the
+    /// organization owning the channel which want the incident emails.
+    pub fn from_channel(
+        conn: &mut ConnType,
+        channel: &NotificationChannels,
+        transport: Arc<EmailTransport>,
+        from: &str,
+        base_url: &str,
+    ) -> Result<Self, ApiError> {
+        let (recipients, oncall) = match &channel.config {
+            ChannelConfig::Email { recipients, oncall } => (recipients, oncall),
+            _ => {
+                return Err(ApiError::InvalidRequestError(Some(format!(
+                    "email: channel {} is not an email channel",
+                    channel.id
+                ))))
+            }
+        };
+
+        let mut recipients = recipients.clone();
+        if let Some(schedule) = oncall {
+            let now = chrono::Utc::now().naive_utc();
+            match OncallSchedules::resolve(conn, &channel.cid, *schedule, now)? {
+                Some(user) if !recipients.contains(&user.email) => recipients.push(user.email),
+                Some(_) => {}
+                None => warn!(
+                    "email: nobody is on call for schedule {} (channel {})",
+                    schedule, channel.id
+                ),
+            }
+        }
+
+        if recipients.is_empty() {
+            recipients =
+                Organizations::get_notified_emails(conn, &channel.cid, EmailKind::Incident)?;
+        }
+
+        Self::new(transport, from, &recipients, base_url)
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn send(&self, incident: &IncidentsJoined, event: IncidentEvent) -> Result<(), ApiError> {
+        let message =
+            IncidentMessage::new(incident, event, &self.base_url).to_email(&self.from, &self.to)?;
+
+        self.transport.send(&message)
+    }
+}
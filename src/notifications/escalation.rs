@@ -0,0 +1,82 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use diesel::Connection;
+
+use super::{Dispatcher, IncidentEvent};
+use crate::apierrors::ApiError;
+use crate::models::{EscalationPolicies, Incidents, NotificationChannels, NotificationDeliveries};
+use crate::ConnType;
+
+impl Dispatcher {
+    /// Queue the escalated event of the incidents having escalation steps due
+    /// - conn: the Database connection
+    /// - now: the current time
+    ///
+    /// Each due step is notified once, even with multiple evaluators running,
+    /// as the escalation_step of the incident is advanced before queuing.
+    /// A failing incident is logged and skipped, so it does not prevent the
+    /// others from being escalated.
+    pub fn escalate(
+        conn: &mut ConnType,
+        now: chrono::NaiveDateTime,
+    ) -> Result<Vec<NotificationDeliveries>, ApiError> {
+        let mut policies: HashMap<(i64, String), Option<EscalationPolicies>> = HashMap::new();
+        let mut queued = Vec::new();
+
+        for incident in Incidents::get_unacknowledged(conn)? {
+            // Incidents of the same alert and host share the same policy
+            let key = (incident.alerts_id, incident.host_uuid.clone());
+            let policy = match policies.entry(key) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => match EscalationPolicies::get_for_incident(conn, &incident)
+                {
+                    Ok(policy) => entry.insert(policy),
+                    Err(err) => {
+                        error!(
+                            "escalation: cannot get the policy of incident {}: {}",
+                            incident.id, err
+                        );
+                        continue;
+                    }
+                },
+            };
+            let policy = match policy {
+                Some(policy) => policy,
+                None => continue,
+            };
+
+            let due = policy.due_steps(&incident, now);
+            if due.is_empty() {
+                continue;
+            }
+
+            let result = conn.transaction(|conn| {
+                if !incident.advance_escalation(conn, incident.escalation_step, due.end as i32)? {
+                    return Ok(Vec::new());
+                }
+
+                let channels = NotificationChannels::get_active_by_ids(
+                    conn,
+                    &incident.cid,
+                    &policy.steps.channels(due),
+                )?;
+                if channels.is_empty() {
+                    return Ok(Vec::new());
+                }
+
+                NotificationDeliveries::enqueue(
+                    conn,
+                    &channels,
+                    &incident,
+                    IncidentEvent::Escalated,
+                )
+            });
+            match result {
+                Ok(deliveries) => queued.extend(deliveries),
+                Err(err) => error!("escalation: incident {} failed: {}", incident.id, err),
+            }
+        }
+
+        Ok(queued)
+    }
+}
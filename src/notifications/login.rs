@@ -0,0 +1,110 @@
+use askama::Template;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::Message;
+
+use super::{human_duration, EmailTransport};
+use crate::apierrors::ApiError;
+use crate::models::{Customers, LoginTokens};
+use crate::ConnType;
+
+/// Content of the email holding the login link of a customer
+pub struct LoginEmail {
+    pub subject: String,
+    pub address: String,
+    pub link: String,
+    // IP address of the client which requested the link
+    pub ip: String,
+    pub expires_in: String,
+}
+
+impl LoginEmail {
+    /// Build the email of the login link
+    /// - address: the email address of the customer
+    /// - token: the plaintext login token
+    /// - client_ip: the IP address of the client which requested the link
+    /// - ttl: for how long the token can be used
+    /// - base_url: the url of the dashboard, used to build the link
+    pub fn new(
+        address: &str,
+        token: &str,
+        client_ip: &str,
+        ttl: chrono::Duration,
+        base_url: &str,
+    ) -> Self {
+        Self {
+            subject: String::from("[sproot] Your login link"),
+            address: address.to_owned(),
+            link: format!(
+                "{}/login/verify?token={}",
+                base_url.trim_end_matches('/'),
+                token
+            ),
+            ip: client_ip.to_owned(),
+            expires_in: human_duration(ttl),
+        }
+    }
+
+    /// Build the email (plain text and html alternatives) for the recipient
+    pub fn to_email(&self, from: &Mailbox, to: &Mailbox) -> Result<Message, ApiError> {
+        Ok(Message::builder()
+            .from(from.clone())
+            .to(to.clone())
+            .subject(&self.subject)
+            .multipart(MultiPart::alternative_plain_html(
+                LoginEmailText { email: self }.render()?,
+                LoginEmailHtml { email: self }.render()?,
+            ))?)
+    }
+
+    /// Create a login token for the customer and send its link by email
+    /// - conn: the Database connection
+    /// - transport: the way the emails are delivered
+    /// - from: the sender of the emails
+    /// - base_url: the url of the dashboard, used to build the link
+    /// - address: the email address given by the client
+    /// - client_ip: the IP address of the client
+    /// - ttl: for how long the link can be used (keep it short)
+    ///
+    /// Return false (without sending anything) when no customer uses the address.
+    /// Nothing is sent either when the customer has too many links pending.
+    /// The services should answer the same in both cases, so the addresses
+    /// of the customers can't be discovered this way.
+    pub fn send_link(
+        conn: &mut ConnType,
+        transport: &EmailTransport,
+        from: &str,
+        base_url: &str,
+        address: &str,
+        client_ip: &str,
+        ttl: chrono::Duration,
+    ) -> Result<bool, ApiError> {
+        let customer = match Customers::get_specific(conn, address) {
+            Ok(customer) => customer,
+            Err(ApiError::DieselError(diesel::result::Error::NotFound)) => return Ok(false),
+            Err(err) => return Err(err),
+        };
+
+        // Answer as if sent once the limit is reached, not to tell the customers apart
+        let token = match LoginTokens::create(conn, &customer.id, client_ip, ttl)? {
+            Some(token) => token,
+            None => return Ok(true),
+        };
+        let message = Self::new(&customer.email, &token, client_ip, ttl, base_url)
+            .to_email(&from.parse()?, &customer.email.parse()?)?;
+        transport.send(&message)?;
+
+        Ok(true)
+    }
+}
+
+#[derive(Template)]
+#[template(path = "email/login.txt")]
+struct LoginEmailText<'a> {
+    email: &'a LoginEmail,
+}
+
+#[derive(Template)]
+#[template(path = "email/login.html")]
+struct LoginEmailHtml<'a> {
+    email: &'a LoginEmail,
+}
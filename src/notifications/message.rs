@@ -0,0 +1,93 @@
+use super::{human_duration, IncidentEvent};
+use crate::models::{IncidentSeverity, IncidentsJoined};
+
+/// Content of the messages sent for an event of an incident
+///
+/// Shared by the notifiers formatting a message (email, chat apps, ...).
+pub struct IncidentMessage {
+    pub event: IncidentEvent,
+    pub subject: String,
+    pub title: &'static str,
+    // Accent color of the message (hex, depends on the severity and event)
+    pub color: &'static str,
+    pub severity: &'static str,
+    pub alert_name: String,
+    pub hostname: String,
+    pub dimension: String,
+    // Hostname and dimension (if any) in a single string (eg: web-01:/dev/sda1)
+    pub target: String,
+    pub value: String,
+    pub started_at: String,
+    pub resolved_at: Option<String>,
+    pub duration: String,
+    // One line summary, short enough to be shown next to a sparkline (or as preview)
+    pub summary: String,
+    pub incident_url: String,
+    pub host_url: String,
+}
+
+impl IncidentMessage {
+    /// Build the content of the message
+    /// - incident: the incident the event is about
+    /// - event: the event to send
+    /// - base_url: the url of the dashboard, used to build the links
+    pub fn new(incident: &IncidentsJoined, event: IncidentEvent, base_url: &str) -> Self {
+        let inner = &incident.incident;
+        let severity = IncidentSeverity::from_i32(inner.severity);
+        let alert_name = incident
+            .alert
+            .as_ref()
+            .map(|alert| alert.name.clone())
+            .unwrap_or_else(|| format!("alert #{}", inner.alerts_id));
+        let duration =
+            human_duration(inner.resolved_at.unwrap_or(inner.updated_at) - inner.started_at);
+
+        let title = match event {
+            IncidentEvent::Opened => "Incident opened",
+            IncidentEvent::Escalated => "Incident escalated",
+            IncidentEvent::Resolved => "Incident resolved",
+        };
+        let color = match (event, severity) {
+            (IncidentEvent::Resolved, _) => "#2da44e",
+            (_, Some(IncidentSeverity::Warning)) => "#bf8700",
+            _ => "#cf222e",
+        };
+        let severity = severity.map_or("unknown", |s| s.as_str());
+        let target = if inner.dimension.is_empty() {
+            inner.hostname.clone()
+        } else {
+            format!("{}:{}", inner.hostname, inner.dimension)
+        };
+        let base_url = base_url.trim_end_matches('/');
+
+        Self {
+            event,
+            subject: format!(
+                "[{}] {}: {} on {}",
+                severity.to_uppercase(),
+                title,
+                alert_name,
+                inner.hostname
+            ),
+            summary: format!(
+                "{} {} {}={} ({}, {})",
+                severity, &target, alert_name, inner.result, event, duration
+            ),
+            title,
+            color,
+            severity,
+            alert_name,
+            hostname: inner.hostname.clone(),
+            dimension: inner.dimension.clone(),
+            target,
+            value: inner.result.clone(),
+            started_at: inner.started_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            resolved_at: inner
+                .resolved_at
+                .map(|at| at.format("%Y-%m-%d %H:%M:%S").to_string()),
+            duration,
+            incident_url: format!("{}/incidents/{}", base_url, inner.id),
+            host_url: format!("{}/hosts/{}", base_url, inner.host_uuid),
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::apierrors::ApiError;
+use crate::models::{
+    BaseCrud, ChannelKind, Incidents, IncidentsJoined, NotificationChannels, NotificationDeliveries,
+};
+use crate::ConnType;
+
+mod alertmanager;
+mod chat;
+mod digest;
+mod email;
+mod escalation;
+mod login;
+mod message;
+mod pagerduty;
+#[cfg(test)]
+mod testing;
+mod webhook;
+pub use alertmanager::*;
+pub use chat::*;
+pub use digest::*;
+pub use email::*;
+pub use login::*;
+pub use message::*;
+pub use pagerduty::*;
+pub use webhook::*;
+
+/// Event happening to an incident, which can be notified to the channels
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, TS)]
+#[serde(rename_all = "lowercase")]
+#[ts(export)]
+pub enum IncidentEvent {
+    Opened,
+    Escalated,
+    Resolved,
+}
+
+impl IncidentEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IncidentEvent::Opened => "opened",
+            IncidentEvent::Escalated => "escalated",
+            IncidentEvent::Resolved => "resolved",
+        }
+    }
+}
+
+impl fmt::Display for IncidentEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for IncidentEvent {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "opened" => Ok(IncidentEvent::Opened),
+            "escalated" => Ok(IncidentEvent::Escalated),
+            "resolved" => Ok(IncidentEvent::Resolved),
+            _ => Err(ApiError::InvalidRequestError(Some(format!(
+                "event: {} is invalid. Valid are: opened, escalated, resolved.",
+                s
+            )))),
+        }
+    }
+}
+
+/// For how long the deliveries taken from the queue are reserved to a worker
+const DELIVERY_LEASE: chrono::Duration = chrono::Duration::minutes(5);
+
+/// Format a duration in a short human readable way (eg: 1h 12m)
+pub fn human_duration(duration: chrono::Duration) -> String {
+    let secs = duration.num_seconds().max(0);
+    let (days, hours, mins) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+
+    match (days, hours, mins) {
+        (0, 0, 0) => format!("{}s", secs),
+        (0, 0, m) => format!("{}m {}s", m, secs % 60),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
+
+/// Key identifying the incidents of an alert for a host and dimension
+///
+/// Stable across the incidents, so the downstream tools (Alertmanager,
+/// PagerDuty, ...) group the opened and resolved events together.
+pub fn dedup_key(incident: &Incidents) -> String {
+    format!(
+        "sproot-{}-{}-{}",
+        incident.alerts_id, incident.host_uuid, incident.dimension
+    )
+}
+
+/// Deliver the events of the incidents to a single channel
+///
+/// Each delivery method (email, webhook, ...) implements this trait,
+/// an instance being built for each channel using its ChannelConfig.
+pub trait Notifier: Send + Sync {
+    fn send(&self, incident: &IncidentsJoined, event: IncidentEvent) -> Result<(), ApiError>;
+}
+
+/// Build the Notifier of a channel from its configuration
+pub type NotifierBuilder = Box<
+    dyn Fn(&mut ConnType, &NotificationChannels) -> Result<Box<dyn Notifier>, ApiError>
+        + Send
+        + Sync,
+>;
+
+/// Result of the delivery of an event to one channel
+#[derive(Debug)]
+pub struct DispatchOutcome {
+    pub channel_id: i64,
+    pub result: Result<(), ApiError>,
+}
+
+/// Resolve the channels an incident event goes to and send it to them
+///
+/// The services register a NotifierBuilder for each kind of channel they
+/// support. Channels of a kind without builder are skipped with a warning.
+#[derive(Default)]
+pub struct Dispatcher {
+    builders: HashMap<ChannelKind, NotifierBuilder>,
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the builder of the Notifier in charge of a kind of channel
+    pub fn register<F>(&mut self, kind: ChannelKind, builder: F) -> &mut Self
+    where
+        F: Fn(&mut ConnType, &NotificationChannels) -> Result<Box<dyn Notifier>, ApiError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.builders.insert(kind, Box::new(builder));
+        self
+    }
+
+    /// Get the channels the event of the incident should be sent to
+    /// - conn: the Database connection
+    /// - incident: the incident the event is about
+    /// - event: the event to send
+    pub fn resolve(
+        conn: &mut ConnType,
+        incident: &IncidentsJoined,
+        event: IncidentEvent,
+    ) -> Result<Vec<NotificationChannels>, ApiError> {
+        NotificationChannels::get_for_incident(conn, &incident.incident, event)
+    }
+
+    /// Send the event of the incident to every channels it should go to
+    /// - conn: the Database connection
+    /// - incident: the incident the event is about
+    /// - event: the event to send
+    ///
+    /// A failing channel does not prevent the others from being notified,
+    /// the result of each delivery is returned instead.
+    pub fn dispatch(
+        &self,
+        conn: &mut ConnType,
+        incident: &IncidentsJoined,
+        event: IncidentEvent,
+    ) -> Result<Vec<DispatchOutcome>, ApiError> {
+        let channels = Self::resolve(conn, incident, event)?;
+
+        let mut outcomes = Vec::with_capacity(channels.len());
+        for channel in channels {
+            if !self.builders.contains_key(&channel.config.kind()) {
+                warn!(
+                    "dispatch: no notifier registered for {:?} (channel {})",
+                    channel.config.kind(),
+                    channel.id
+                );
+                continue;
+            }
+
+            let result = self.deliver(conn, &channel, incident, event);
+            if let Err(err) = &result {
+                error!("dispatch: channel {} failed: {}", channel.id, err);
+            }
+
+            outcomes.push(DispatchOutcome {
+                channel_id: channel.id,
+                result,
+            });
+        }
+
+        Ok(outcomes)
+    }
+
+    /// Queue the event of the incident for every channels it should go to
+    /// - conn: the Database connection
+    /// - incident: the incident the event is about
+    /// - event: the event to send
+    ///
+    /// The deliveries are then sent by the workers calling process_queue.
+    pub fn enqueue(
+        conn: &mut ConnType,
+        incident: &IncidentsJoined,
+        event: IncidentEvent,
+    ) -> Result<Vec<NotificationDeliveries>, ApiError> {
+        let channels = Self::resolve(conn, incident, event)?;
+        if channels.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        NotificationDeliveries::enqueue(conn, &channels, &incident.incident, event)
+    }
+
+    /// Send the due deliveries of the queue, recording the outcome of each
+    /// - conn: the Database connection
+    /// - size: how many deliveries to send at most
+    /// - retry: when the failed deliveries are retried (and how many times)
+    ///
+    /// Safe to be called by multiple workers at once. The notifiers should
+    /// not retry by themselves (max_attempts of 1) as the queue does it.
+    /// Return the number of deliveries successfully sent.
+    pub fn process_queue(
+        &self,
+        conn: &mut ConnType,
+        size: i64,
+        retry: &RetryPolicy,
+    ) -> Result<usize, ApiError> {
+        let deliveries = NotificationDeliveries::claim(conn, size, DELIVERY_LEASE)?;
+
+        let mut delivered = 0;
+        for delivery in deliveries {
+            let result = delivery.event.parse::<IncidentEvent>().and_then(|event| {
+                let channel = NotificationChannels::get_specific(conn, delivery.channel_id)?;
+                let incident = Incidents::get_joined(conn, delivery.incident_id)?;
+                self.deliver(conn, &channel, &incident, event)
+            });
+
+            match result {
+                Ok(_) => {
+                    delivery.mark_delivered(conn)?;
+                    delivered += 1;
+                }
+                Err(err) => {
+                    error!("queue: delivery {} failed: {}", delivery.id, err);
+                    delivery.mark_failed(conn, &err, retry)?;
+                }
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    /// Build the Notifier of the channel and send the event with it
+    fn deliver(
+        &self,
+        conn: &mut ConnType,
+        channel: &NotificationChannels,
+        incident: &IncidentsJoined,
+        event: IncidentEvent,
+    ) -> Result<(), ApiError> {
+        let builder = self.builders.get(&channel.config.kind()).ok_or_else(|| {
+            ApiError::ServerError(Some(format!(
+                "dispatch: no notifier registered for {:?}",
+                channel.config.kind()
+            )))
+        })?;
+
+        builder(conn, channel).and_then(|notifier| notifier.send(incident, event))
+    }
+}
@@ -0,0 +1,238 @@
+use chrono::SecondsFormat;
+use serde::{Deserialize, Serialize};
+
+use super::{dedup_key, IncidentEvent, IncidentMessage, Notifier, WebhookSender};
+use crate::apierrors::ApiError;
+use crate::models::{ChannelConfig, IncidentSeverity, IncidentsJoined, NotificationChannels};
+
+/// Endpoint of the PagerDuty Events API v2
+pub const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// Event of the PagerDuty Events API v2
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PagerDutyEvent {
+    pub routing_key: String,
+    // trigger or resolve
+    pub event_action: String,
+    pub dedup_key: String,
+    // Only needed by the trigger events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<PagerDutyPayload>,
+    pub client: String,
+    pub client_url: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<PagerDutyLink>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PagerDutyPayload {
+    pub summary: String,
+    pub source: String,
+    // critical, error, warning or info
+    pub severity: String,
+    pub timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub component: Option<String>,
+    pub group: String,
+    pub custom_details: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PagerDutyLink {
+    pub href: String,
+    pub text: String,
+}
+
+impl PagerDutyEvent {
+    /// Render the event of the incident as a PagerDuty event
+    /// - routing_key: the integration key of the PagerDuty service
+    /// - incident: the incident the event is about
+    /// - event: the event to send
+    /// - base_url: the url of the dashboard, used to build the links
+    ///
+    /// Opened and escalated are sent as trigger (updating the PagerDuty
+    /// incident with the same dedup_key), resolved as resolve.
+    pub fn new(
+        routing_key: &str,
+        incident: &IncidentsJoined,
+        event: IncidentEvent,
+        base_url: &str,
+    ) -> Self {
+        let inner = &incident.incident;
+        let message = IncidentMessage::new(incident, event, base_url);
+
+        let payload = match event {
+            IncidentEvent::Resolved => None,
+            _ => Some(PagerDutyPayload {
+                summary: message.summary.clone(),
+                source: inner.hostname.clone(),
+                severity: match IncidentSeverity::from_i32(inner.severity) {
+                    Some(IncidentSeverity::Warning) => String::from("warning"),
+                    Some(IncidentSeverity::Critical) => String::from("critical"),
+                    None => String::from("error"),
+                },
+                timestamp: inner
+                    .started_at
+                    .and_utc()
+                    .to_rfc3339_opts(SecondsFormat::Secs, true),
+                component: (!inner.dimension.is_empty()).then(|| inner.dimension.clone()),
+                group: message.alert_name.clone(),
+                custom_details: serde_json::json!({
+                    "value": inner.result,
+                    "host_uuid": inner.host_uuid,
+                    "alert_id": inner.alerts_id,
+                    "incident_id": inner.id,
+                    "duration": message.duration,
+                }),
+            }),
+        };
+
+        Self {
+            routing_key: routing_key.to_owned(),
+            event_action: String::from(match event {
+                IncidentEvent::Resolved => "resolve",
+                _ => "trigger",
+            }),
+            dedup_key: dedup_key(inner),
+            payload,
+            client: String::from("Speculare"),
+            client_url: message.incident_url.clone(),
+            links: vec![
+                PagerDutyLink {
+                    href: message.incident_url,
+                    text: String::from("View the incident"),
+                },
+                PagerDutyLink {
+                    href: message.host_url,
+                    text: String::from("View the host"),
+                },
+            ],
+        }
+    }
+}
+
+/// Send the incidents events to the PagerDuty Events API v2
+pub struct PagerDutyNotifier {
+    sender: WebhookSender,
+    routing_key: String,
+    url: String,
+    base_url: String,
+}
+
+impl PagerDutyNotifier {
+    pub fn new(sender: WebhookSender, routing_key: &str, url: &str, base_url: &str) -> Self {
+        Self {
+            sender,
+            routing_key: routing_key.to_owned(),
+            url: url.to_owned(),
+            base_url: base_url.to_owned(),
+        }
+    }
+
+    /// Create the notifier of a PagerDuty channel
+    /// - channel: the pagerduty channel
+    /// - sender: the sender used to POST the events
+    /// - base_url: the url of the dashboard, used to build the links
+    pub fn from_channel(
+        channel: &NotificationChannels,
+        sender: WebhookSender,
+        base_url: &str,
+    ) -> Result<Self, ApiError> {
+        match &channel.config {
+            ChannelConfig::Pagerduty { routing_key, url } => Ok(Self::new(
+                sender,
+                routing_key,
+                url.as_deref().unwrap_or(PAGERDUTY_EVENTS_URL),
+                base_url,
+            )),
+            _ => Err(ApiError::InvalidRequestError(Some(format!(
+                "pagerduty: channel {} is not a pagerduty channel",
+                channel.id
+            )))),
+        }
+    }
+}
+
+impl Notifier for PagerDutyNotifier {
+    fn send(&self, incident: &IncidentsJoined, event: IncidentEvent) -> Result<(), ApiError> {
+        let body = serde_json::to_vec(&PagerDutyEvent::new(
+            &self.routing_key,
+            incident,
+            event,
+            &self.base_url,
+        ))?;
+
+        self.sender.post(&self.url, &body, None, &[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::testing::{incident, resolved};
+
+    const BASE_URL: &str = "https://sproot.local/";
+
+    #[test]
+    fn trigger_event() {
+        let event = PagerDutyEvent::new(
+            "routing",
+            &incident("cpu usage", "sda1"),
+            IncidentEvent::Opened,
+            BASE_URL,
+        );
+        let value = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(value["routing_key"], "routing");
+        assert_eq!(value["event_action"], "trigger");
+        assert_eq!(value["dedup_key"], "sproot-7-host-uuid-sda1");
+        assert_eq!(value["client_url"], "https://sproot.local/incidents/1");
+        assert_eq!(
+            value["links"][1]["href"],
+            "https://sproot.local/hosts/host-uuid"
+        );
+        assert_eq!(value["payload"]["source"], "web_01");
+        assert_eq!(value["payload"]["severity"], "critical");
+        assert_eq!(value["payload"]["timestamp"], "2023-11-14T22:13:20Z");
+        assert_eq!(value["payload"]["component"], "sda1");
+        assert_eq!(value["payload"]["group"], "cpu usage");
+        assert_eq!(value["payload"]["custom_details"]["value"], "92");
+        assert_eq!(value["payload"]["custom_details"]["incident_id"], 1);
+    }
+
+    #[test]
+    fn escalated_is_a_trigger() {
+        let event = PagerDutyEvent::new(
+            "routing",
+            &incident("cpu usage", ""),
+            IncidentEvent::Escalated,
+            BASE_URL,
+        );
+        let value = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(value["event_action"], "trigger");
+        assert!(value["payload"].get("component").is_none());
+    }
+
+    #[test]
+    fn resolve_event() {
+        let opened = PagerDutyEvent::new(
+            "routing",
+            &incident("cpu usage", ""),
+            IncidentEvent::Opened,
+            BASE_URL,
+        );
+        let event = PagerDutyEvent::new(
+            "routing",
+            &resolved(incident("cpu usage", "")),
+            IncidentEvent::Resolved,
+            BASE_URL,
+        );
+        let value = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(value["event_action"], "resolve");
+        // Resolve the PagerDuty incident opened by the trigger
+        assert_eq!(value["dedup_key"], opened.dedup_key.as_str());
+        assert!(value.get("payload").is_none());
+    }
+}
@@ -0,0 +1,61 @@
+use uuid::Uuid;
+
+use crate::models::{Alerts, IncidentSeverity, IncidentStatus, Incidents, IncidentsJoined};
+
+/// Incident (critical, opened 5 minutes ago) of the alert, shared by the tests of the notifiers
+/// - alert_name: the name of the alert of the incident
+/// - dimension: the sub-target of the alert, empty if none
+pub fn incident(alert_name: &str, dimension: &str) -> IncidentsJoined {
+    let started_at = chrono::DateTime::from_timestamp(1_700_000_000, 0)
+        .unwrap()
+        .naive_utc();
+    let alert = Alerts {
+        id: 7,
+        active: true,
+        name: alert_name.to_owned(),
+        table: String::from("cpu"),
+        lookup: String::from("avg abs 5m of usage"),
+        timing: 60,
+        warn: String::from("$this > 50"),
+        crit: String::from("$this > 80"),
+        info: Some(String::from("The cpu is busy")),
+        host_uuid: String::from("host-uuid"),
+        cid: Uuid::nil(),
+        hostname: String::from("web_01"),
+        where_clause: None,
+        flap_window: None,
+        flap_threshold: None,
+        deleted_at: None,
+    };
+    let incident = Incidents {
+        id: 1,
+        result: String::from("92"),
+        started_at,
+        updated_at: started_at + chrono::Duration::minutes(5),
+        resolved_at: None,
+        host_uuid: String::from("host-uuid"),
+        hostname: String::from("web_01"),
+        status: IncidentStatus::Active as i32,
+        severity: IncidentSeverity::Critical as i32,
+        alerts_id: 7,
+        cid: Uuid::nil(),
+        acknowledged_at: None,
+        flapping: false,
+        flap_changes: 0,
+        flap_changed_at: None,
+        dimension: dimension.to_owned(),
+        escalation_step: 0,
+    };
+
+    IncidentsJoined::from((incident, Some(alert)))
+}
+
+/// Same incident, resolved 10 minutes after it started
+pub fn resolved(mut incident: IncidentsJoined) -> IncidentsJoined {
+    let resolved_at = incident.incident.started_at + chrono::Duration::minutes(10);
+    incident.incident.resolved_at = Some(resolved_at);
+    incident.incident.updated_at = resolved_at;
+    incident.incident.status = IncidentStatus::Resolved as i32;
+
+    incident
+}